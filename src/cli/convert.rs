@@ -0,0 +1,97 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::process_convert;
+
+use super::{parse_ascii_char, validate_file, CmdExecutor, OutputFormat};
+
+/// 反向转换选项 将Json,Yaml,Toml,Ndjson等结构化数据转换为表格
+#[derive(Debug, Clone, Parser)]
+pub struct ConvertOpts {
+    /// 输入文件
+    #[arg(short, long, value_parser = validate_file)]
+    pub input: String,
+    /// 输出路径 会自动加上格式后缀，- 表示标准输出
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 输出格式 支持csv,xlsx,markdown(md)和html，xlsx保留数字、布尔值和日期的类型
+    #[arg(long, default_value = "csv", value_parser = parse_convert_target)]
+    pub to: OutputFormat,
+    /// 输入文件格式 默认根据后缀判断，支持json,ndjson(jsonl),yaml,toml,msgpack,cbor,xml和markdown
+    #[arg(long)]
+    pub from: Option<OutputFormat>,
+    /// csv输出时写入UTF-8 BOM 便于Excel正确识别编码
+    #[arg(long, default_value_t = false)]
+    pub bom: bool,
+    /// 输出的分隔符
+    #[arg(short, long, default_value = ",", value_parser = parse_ascii_char)]
+    pub delimiter: char,
+    /// 列名来源 first为第一条记录，union为所有记录的并集
+    #[arg(long, default_value = "first")]
+    pub headers: HeaderMode,
+    /// 嵌套对象列名的连接符
+    #[arg(long, default_value = ".")]
+    pub key_separator: String,
+    /// 数组合并为一个单元格时使用的分隔符 不指定时展开为 tags[0] 形式的列
+    #[arg(long)]
+    pub array_separator: Option<String>,
+}
+
+impl CmdExecutor for ConvertOpts {
+    async fn execute(self) -> Result<()> {
+        let output = match self.output.as_deref() {
+            Some("-") => "-".to_string(),
+            output => format!("{}.{}", output.unwrap_or("output"), self.to.extension()),
+        };
+        process_convert(&self, &output)
+    }
+}
+
+// 转换的目标格式 只支持表格
+fn parse_convert_target(format: &str) -> Result<OutputFormat> {
+    match format.parse::<OutputFormat>()? {
+        format if format.is_tabular() => Ok(format),
+        format => Err(anyhow::anyhow!(
+            "Cannot convert to {}, use csv, xlsx, markdown or html",
+            format
+        )),
+    }
+}
+
+/// 列名来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderMode {
+    /// 第一条记录的列
+    First,
+    /// 所有记录的列的并集
+    Union,
+}
+
+impl FromStr for HeaderMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "first" => Ok(HeaderMode::First),
+            "union" => Ok(HeaderMode::Union),
+            _ => Err(anyhow::anyhow!("Invalid HeaderMode")),
+        }
+    }
+}
+
+impl From<HeaderMode> for &'static str {
+    fn from(value: HeaderMode) -> Self {
+        match value {
+            HeaderMode::First => "first",
+            HeaderMode::Union => "union",
+        }
+    }
+}
+
+impl Display for HeaderMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&'static str>::into(*self))
+    }
+}
@@ -1,74 +1,720 @@
-use std::{fmt::Display, str::FromStr};
+use std::{
+    fmt::Display,
+    ops::Deref,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use clap::Parser;
+use clap::{builder::RangedU64ValueParser, ArgAction, ArgGroup, Parser};
+use encoding_rs::Encoding;
 
-use crate::process_csv;
+use crate::{
+    find_format, formats, process_csv, process_csv_agg, process_csv_cat, process_csv_diff,
+    process_csv_join, process_csv_query, process_csv_split, process_csv_stats,
+    process_csv_validate, process_csv_view, write_csv_stats, DiffSummary, Format, ValidationReport,
+};
 
-use super::{validate_file, CmdExecutor};
+use super::{exit_with_status, parse_ascii_char, validate_file, validate_path, CmdExecutor};
 use anyhow::Result;
 
+/// CSV命令 不指定子命令时将CSV转换为其他格式
+#[derive(Debug, Clone, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct CsvCommand {
+    /// 子命令
+    #[command(subcommand)]
+    pub command: Option<CsvSubCommand>,
+    /// 转换选项
+    #[command(flatten)]
+    pub convert: Option<CsvOpts>,
+}
+
+impl CmdExecutor for CsvCommand {
+    async fn execute(self) -> Result<()> {
+        match (self.command, self.convert) {
+            (Some(command), _) => command.execute().await,
+            (None, Some(convert)) => convert.execute().await,
+            (None, None) => Err(anyhow::anyhow!("Missing --input or subcommand")),
+        }
+    }
+}
+
+/// CSV子命令
+#[derive(Debug, Clone, Parser)]
+#[enum_dispatch::enum_dispatch(CmdExecutor)]
+pub enum CsvSubCommand {
+    /// 统计每一列的类型、空值、不同值、数值分布和高频值
+    Stats(CsvStatsOpts),
+    /// 分组聚合 如 --group-by Position --agg 'count(*),max(Kit Number)'，支持透视表
+    Agg(CsvAggOpts),
+    /// 使用SQL查询一个或多个CSV文件 如 SELECT Position, count(*) FROM players GROUP BY Position
+    Query(CsvQueryOpts),
+    /// 按主键比较两个CSV 输出新增、删除和修改的行，有差异时退出码为1
+    Diff(CsvDiffOpts),
+    /// 按主键连接两个CSV 支持inner,left和outer
+    Join(CsvJoinOpts),
+    /// 拼接多个CSV 按列名对齐，缺少的列为空
+    Cat(CsvCatOpts),
+    /// 按行数或某一列的值拆分为多个文件
+    Split(CsvSplitOpts),
+    /// 按Schema校验每一行 输出所有违规的行号、列和原因，有违规时退出码为1
+    Validate(CsvValidateOpts),
+    /// 在终端中以对齐的表格查看 终端中可以翻页、左右滚动和搜索
+    View(CsvViewOpts),
+}
+
 /// CSV选项
 #[derive(Debug, Clone, Parser)]
 pub struct CsvOpts {
-    /// 输入文件 .csv
-    #[arg(short, long,value_parser=validate_file)]
+    // clap不会为含有flatten的结构体填充ArgGroup，这里手动加入，CsvCommand据此判断是否为转换
+    /// 输入文件 .csv，也支持.xlsx,.xls和.ods
+    #[arg(short, long,value_parser=validate_file, group = "CsvOpts")]
     pub input: String,
-    /// 输出路径
+    /// 输出路径 会自动加上格式后缀，- 表示标准输出
     #[arg(short, long)]
     pub output: Option<String>,
-    /// 输出文件格式 ，支持json和yaml
+    /// 输出文件格式 ，支持json,ndjson(jsonl),yaml,toml,csv,sql,sqlite,xlsx,
+    /// msgpack,cbor,xml,markdown(md)和html
     #[arg(long,default_value="json",value_parser = parse_output_format )]
     pub format: OutputFormat,
-    /// 分隔符
-    #[arg(short, long, default_value_t = ',')]
+    /// 读取选项
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+    /// SQL输出选项
+    #[command(flatten)]
+    pub sql: SqlOpts,
+    /// 列保护选项
+    #[command(flatten)]
+    pub protect: ProtectOpts,
+    /// 模板输出选项
+    #[command(flatten)]
+    pub template: TemplateOpts,
+    /// XML输出选项
+    #[command(flatten)]
+    pub xml: XmlOpts,
+    /// 行过滤表达式 如 'Nationality == "Italy" and `Kit Number` > 25'
+    /// 支持 == != < <= > >=、and or not、contains、=~、in [...]、is [not] null
+    #[arg(long = "where")]
+    pub filter: Option<String>,
+    /// 选择输出的列并按此顺序输出 支持列名或从1开始的序号
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+    /// 排除的列 支持列名或从1开始的序号
+    #[arg(long, visible_alias = "drop", value_delimiter = ',')]
+    pub exclude: Vec<String>,
+    /// 重命名列 如 Name=player
+    #[arg(long, value_delimiter = ',', value_parser = parse_rename)]
+    pub rename: Vec<(String, String)>,
+    /// 是否推断列类型 如整数、浮点数、布尔值、空值和日期，sql和sqlite格式总是推断
+    #[arg(long, default_value_t = false)]
+    pub infer: bool,
+    /// 用于推断类型的行数 0表示扫描全部行，从标准输入读取时必须大于0
+    #[arg(long, default_value_t = 0)]
+    pub infer_rows: usize,
+    /// 指定列类型 如 Age:int,DOB:date，优先于推断结果
+    #[arg(long, value_delimiter = ',', value_parser = parse_column_type)]
+    pub types: Vec<(String, ColumnType)>,
+    /// 将 address.city 或 tags[0] 形式的列名还原为嵌套对象和数组
+    #[arg(long, default_value_t = false)]
+    pub unflatten: bool,
+    /// 嵌套列名的连接符
+    #[arg(long, default_value = ".")]
+    pub key_separator: String,
+    /// csv格式输出时写入UTF-8 BOM 便于Excel正确识别编码
+    #[arg(long, default_value_t = false)]
+    pub bom: bool,
+    /// 宽松模式 跳过列数不符或类型转换失败的行，并写入 --rejects 文件
+    #[arg(long, default_value_t = false)]
+    pub lenient: bool,
+    /// 宽松模式下被跳过的行 附带行号和原因
+    #[arg(long, default_value = "rejects.csv", requires = "lenient")]
+    pub rejects: String,
+}
+
+/// CSV读取选项 供所有CSV相关命令复用
+#[derive(Debug, Clone, Parser)]
+pub struct CsvReaderOpts {
+    /// 分隔符 支持 \t 表示制表符
+    #[arg(short, long, default_value = ",", value_parser = parse_ascii_char)]
     pub delimiter: char,
     /// 是否有Header
-    #[arg(long, default_value_t = true)]
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
     pub header: bool,
+    /// 自定义列名 逗号分隔，没有Header时默认生成 col_1,col_2...
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Option<Vec<String>>,
+    /// 引号字符
+    #[arg(long, default_value = "\"", value_parser = parse_ascii_char)]
+    pub quote: char,
+    /// 是否关闭引号处理
+    #[arg(long, default_value_t = false)]
+    pub no_quoting: bool,
+    /// 转义字符 默认使用双引号转义
+    #[arg(long, value_parser = parse_ascii_char)]
+    pub escape: Option<char>,
+    /// 注释字符 以该字符开头的行将被忽略
+    #[arg(long, value_parser = parse_ascii_char)]
+    pub comment: Option<char>,
+    /// 去除空白 支持none,headers,fields,all
+    #[arg(long, default_value = "none")]
+    pub trim: CsvTrim,
+    /// 输入编码 如utf-8,gbk,gb18030,shift_jis,latin1，auto为自动检测，有BOM时以BOM为准
+    #[arg(long, default_value = "auto")]
+    pub encoding: CsvEncoding,
+    /// 读取.xlsx,.xls,.ods时的工作表 名称或从1开始的序号，默认第一个
+    #[arg(long)]
+    pub sheet: Option<String>,
+    /// 读取工作表的单元格范围 如 A1:D20，只写起点时读到末尾
+    #[arg(long)]
+    pub range: Option<String>,
+}
+
+/// 列保护选项 在输出前对敏感列进行处理，列名为原始列名
+#[derive(Debug, Clone, Parser)]
+pub struct ProtectOpts {
+    /// 加密的列 使用与 rcli text encrypt 相同的ChaCha20
+    #[arg(long, value_delimiter = ',')]
+    pub encrypt: Vec<String>,
+    /// 解密的列 还原 --encrypt 的结果
+    #[arg(long, value_delimiter = ',')]
+    pub decrypt: Vec<String>,
+    /// ChaCha20 Key文件 可由 rcli text generate --format chacha20 生成
+    #[arg(long, value_parser = validate_file)]
+    pub encrypt_key: Option<String>,
+    /// 假名化的列 相同的值和Key总是得到相同的结果，可以继续用于连接
+    #[arg(long, value_delimiter = ',')]
+    pub pseudonymize: Vec<String>,
+    /// Blake3 Key文件 可由 rcli text generate --format blake3 生成
+    #[arg(long, value_parser = validate_file)]
+    pub hash_key: Option<String>,
+    /// 部分遮盖的列 如 ****1234
+    #[arg(long, value_delimiter = ',')]
+    pub mask: Vec<String>,
+    /// 遮盖时保留末尾的字符数
+    #[arg(long, default_value_t = 4)]
+    pub mask_keep: usize,
+}
+
+/// 模板输出选项 使用Tera模板渲染，输出路径不再加后缀
+#[derive(Debug, Clone, Parser)]
+pub struct TemplateOpts {
+    /// Tera模板文件 可以使用 headers、records、stats 和 count 渲染全部记录，
+    /// 如 .html 和 .xml 模板会自动转义
+    #[arg(long, value_parser = validate_file, conflicts_with = "format")]
+    pub template: Option<String>,
+    /// 每条记录渲染为一个文件 参数为文件名模板，如 '{{ record.Name | slugify }}.md'，
+    /// 模板中可以使用 record、index 和 headers，--output 为输出目录
+    #[arg(long, requires = "template")]
+    pub per_row: Option<String>,
+}
+
+/// XML输出选项 不合法的元素名中的字符替换为_
+#[derive(Debug, Clone, Parser)]
+pub struct XmlOpts {
+    /// 根元素名
+    #[arg(long, default_value = "rows")]
+    pub xml_root: String,
+    /// 每条记录的元素名
+    #[arg(long, default_value = "row")]
+    pub xml_row: String,
+}
+
+/// SQL输出选项
+#[derive(Debug, Clone, Parser)]
+pub struct SqlOpts {
+    /// 表名 默认使用输入文件名
+    #[arg(long)]
+    pub table_name: Option<String>,
+    /// SQL方言 支持postgres,mysql和sqlite
+    #[arg(long, default_value = "postgres")]
+    pub dialect: SqlDialect,
+    /// 每条INSERT语句包含的行数
+    #[arg(long, default_value_t = 500, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub batch_size: usize,
 }
 
 /// 当前Opts的执行逻辑
 impl CmdExecutor for CsvOpts {
     async fn execute(self) -> Result<()> {
-        let output = self.output.unwrap_or_else(|| "output".to_string());
-        let output = format!("{}.{}", output, self.format);
-        process_csv(&self.input, &output, self.format)
+        let output = match (self.output.as_deref(), &self.template.template) {
+            (Some("-"), _) => "-".to_string(),
+            // 模板输出使用原样的路径 逐行渲染时为输出目录
+            (Some(output), Some(_)) => output.to_string(),
+            (None, Some(_)) if self.template.per_row.is_some() => ".".to_string(),
+            (None, Some(_)) => "-".to_string(),
+            (output, None) => format!("{}.{}", output.unwrap_or("output"), self.format.extension()),
+        };
+        process_csv(&self, &output)
     }
 }
 
-/// 输出格式
-#[derive(Debug, Clone, Copy)]
-pub enum OutputFormat {
-    /// Json格式
+/// 统计选项
+#[derive(Debug, Clone, Parser)]
+pub struct CsvStatsOpts {
+    /// 输入文件 .csv
+    #[arg(short, long, value_parser = validate_file)]
+    pub input: String,
+    /// 输出路径 默认为标准输出
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// 输出格式 不指定时输出表格
+    #[arg(long, value_parser = parse_output_format)]
+    pub format: Option<OutputFormat>,
+    /// 每列输出的高频值个数
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+    /// 读取选项
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvStatsOpts {
+    async fn execute(self) -> Result<()> {
+        let stats = process_csv_stats(&self)?;
+        write_csv_stats(&stats, &self)
+    }
+}
+
+/// 分组聚合选项
+#[derive(Debug, Clone, Parser)]
+pub struct CsvAggOpts {
+    /// 输入文件 .csv
+    #[arg(short, long, value_parser = validate_file)]
+    pub input: String,
+    /// 输出路径 默认为标准输出
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// 输出格式 不指定时输出CSV
+    #[arg(long, value_parser = parse_output_format)]
+    pub format: Option<OutputFormat>,
+    /// 分组列 支持列名或从1开始的序号，不指定时对全部行聚合
+    #[arg(long, value_delimiter = ',')]
+    pub group_by: Vec<String>,
+    /// 聚合表达式 支持count,sum,avg,min,max,distinct，如 count(*),avg(Age) as mean_age
+    /// 不指定时为 count(*)
+    #[arg(long, value_delimiter = ',', value_parser = parse_aggregate)]
+    pub agg: Vec<Aggregate>,
+    /// 透视列 该列的每个不同值都会展开为新的列
+    #[arg(long)]
+    pub pivot: Option<String>,
+    /// 行过滤表达式 语法与转换时的 --where 相同
+    #[arg(long = "where")]
+    pub filter: Option<String>,
+    /// 读取选项
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvAggOpts {
+    async fn execute(self) -> Result<()> {
+        process_csv_agg(&self)
+    }
+}
+
+/// SQL查询选项
+#[derive(Debug, Clone, Parser)]
+pub struct CsvQueryOpts {
+    /// SQL语句 使用SQLite语法，列名包含空格时用双引号，如 "Kit Number"
+    pub sql: String,
+    /// 加载的表 格式为 表名=文件，只写文件时使用文件名作为表名，可以指定多次用于连接
+    #[arg(short, long = "table", required = true, value_parser = parse_table)]
+    pub tables: Vec<(String, String)>,
+    /// 输出路径 默认为标准输出
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// 输出格式 不指定时输出表格
+    #[arg(long, value_parser = parse_output_format)]
+    pub format: Option<OutputFormat>,
+    /// 读取选项 对所有表生效
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvQueryOpts {
+    async fn execute(self) -> Result<()> {
+        process_csv_query(&self)
+    }
+}
+
+/// 比较选项
+#[derive(Debug, Clone, Parser)]
+pub struct CsvDiffOpts {
+    /// 旧文件
+    #[arg(value_parser = validate_file)]
+    pub old: String,
+    /// 新文件
+    #[arg(value_parser = validate_file)]
+    pub new: String,
+    /// 主键列 多个列组成联合主键
+    #[arg(short, long, value_delimiter = ',', required = true)]
+    pub key: Vec<String>,
+    /// 两个文件都已按主键升序排列 逐行归并比较，内存占用与文件大小无关
+    /// 数字按数值比较，其余按字符串比较
+    #[arg(long, default_value_t = false)]
+    pub sorted: bool,
+    /// 输出路径 默认为标准输出
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// 输出格式 不指定时输出表格
+    #[arg(long, value_parser = parse_output_format)]
+    pub format: Option<OutputFormat>,
+    /// 不使用颜色 默认只在终端中输出颜色
+    #[arg(long, default_value_t = false)]
+    pub no_color: bool,
+    /// 读取选项 对两个文件都生效
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvDiffOpts {
+    async fn execute(self) -> Result<()> {
+        exit_with_status(process_csv_diff(&self), DiffSummary::is_empty)
+    }
+}
+
+/// 连接选项
+#[derive(Debug, Clone, Parser)]
+pub struct CsvJoinOpts {
+    /// 左表
+    #[arg(value_parser = validate_file)]
+    pub left: String,
+    /// 右表
+    #[arg(value_parser = validate_file)]
+    pub right: String,
+    /// 主键列 多个列组成联合主键
+    #[arg(short, long, value_delimiter = ',', required = true)]
+    pub key: Vec<String>,
+    /// 右表的主键列 不指定时与 --key 相同
+    #[arg(long, value_delimiter = ',')]
+    pub right_key: Vec<String>,
+    /// 连接方式 支持inner,left和outer
+    #[arg(long = "type", default_value = "inner")]
+    pub kind: JoinKind,
+    /// 两个文件都已按主键升序排列 逐行归并，否则右表会被放入内存
+    #[arg(long, default_value_t = false)]
+    pub sorted: bool,
+    /// 输出路径 默认为标准输出
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// 输出格式 不指定时输出CSV
+    #[arg(long, value_parser = parse_output_format)]
+    pub format: Option<OutputFormat>,
+    /// 读取选项 对两个文件都生效
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvJoinOpts {
+    async fn execute(self) -> Result<()> {
+        process_csv_join(&self)
+    }
+}
+
+/// 拼接选项
+#[derive(Debug, Clone, Parser)]
+pub struct CsvCatOpts {
+    /// 输入文件 按顺序拼接
+    #[arg(required = true, value_parser = validate_file)]
+    pub inputs: Vec<String>,
+    /// 输出路径 默认为标准输出
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// 输出格式 不指定时输出CSV
+    #[arg(long, value_parser = parse_output_format)]
+    pub format: Option<OutputFormat>,
+    /// 读取选项 对所有文件都生效
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvCatOpts {
+    async fn execute(self) -> Result<()> {
+        process_csv_cat(&self)
+    }
+}
+
+/// 拆分选项
+#[derive(Debug, Clone, Parser)]
+#[command(group = ArgGroup::new("mode").required(true).args(["rows", "by"]))]
+pub struct CsvSplitOpts {
+    /// 输入文件 .csv
+    #[arg(short, long, value_parser = validate_file)]
+    pub input: String,
+    /// 每个文件的行数 不含Header
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub rows: Option<usize>,
+    /// 按该列的值拆分 每个值一个文件
+    #[arg(long)]
+    pub by: Option<String>,
+    /// 输出目录
+    #[arg(short, long, default_value = ".", value_parser = validate_path)]
+    pub output_dir: PathBuf,
+    /// 输出文件名前缀 默认使用输入文件名
+    #[arg(long)]
+    pub prefix: Option<String>,
+    /// 读取选项
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvSplitOpts {
+    async fn execute(self) -> Result<()> {
+        let files = process_csv_split(&self)?;
+        tracing::info!("拆分为 {} 个文件", files.len());
+        Ok(())
+    }
+}
+
+/// 校验选项
+#[derive(Debug, Clone, Parser)]
+pub struct CsvValidateOpts {
+    /// 输入文件 .csv
+    #[arg(short, long, value_parser = validate_file)]
+    pub input: String,
+    /// Schema文件 .yaml或.json，声明每一列的类型、是否可空、正则、枚举值、范围和唯一性
+    #[arg(long, value_parser = validate_file)]
+    pub schema: String,
+    /// 输出路径 默认为标准输出
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// 报告格式 支持text和json
+    #[arg(long, default_value = "text")]
+    pub format: ReportFormat,
+    /// 读取选项
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvValidateOpts {
+    async fn execute(self) -> Result<()> {
+        exit_with_status(process_csv_validate(&self), ValidationReport::is_valid)
+    }
+}
+
+/// 查看选项
+#[derive(Debug, Clone, Parser)]
+pub struct CsvViewOpts {
+    /// 输入文件 .csv，也支持.xlsx,.xls和.ods
+    #[arg(value_parser = validate_file)]
+    pub input: String,
+    /// 只查看前N行
+    #[arg(long, conflicts_with_all = ["tail", "sample"])]
+    pub head: Option<usize>,
+    /// 只查看最后N行
+    #[arg(long, conflicts_with = "sample")]
+    pub tail: Option<usize>,
+    /// 随机抽取N行 按原顺序显示
+    #[arg(long)]
+    pub sample: Option<usize>,
+    /// 单元格的最大显示宽度 超出部分截断
+    #[arg(long, default_value_t = 40, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_width: usize,
+    /// 终端中也直接输出 不进入交互界面
+    #[arg(long)]
+    pub no_pager: bool,
+    /// 读取选项
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+impl CmdExecutor for CsvViewOpts {
+    async fn execute(self) -> Result<()> {
+        process_csv_view(&self)
+    }
+}
+
+/// 校验报告格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// 每行一条违规
+    Text,
+    /// Json对象
     Json,
-    /// Yaml格式
-    Yaml,
-    /// Toml格式
-    Toml,
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid ReportFormat")),
+        }
+    }
+}
+
+impl From<ReportFormat> for &'static str {
+    fn from(value: ReportFormat) -> Self {
+        match value {
+            ReportFormat::Text => "text",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+impl Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&'static str>::into(*self))
+    }
+}
+
+/// 连接方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    /// 只保留两边都有的行
+    Inner,
+    /// 保留左表的全部行
+    Left,
+    /// 保留两边的全部行
+    Outer,
+}
+
+impl FromStr for JoinKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            "outer" | "full" => Ok(JoinKind::Outer),
+            _ => Err(anyhow::anyhow!("Invalid JoinKind")),
+        }
+    }
+}
+
+impl From<JoinKind> for &'static str {
+    fn from(value: JoinKind) -> Self {
+        match value {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+            JoinKind::Outer => "outer",
+        }
+    }
+}
+
+impl Display for JoinKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&'static str>::into(*self))
+    }
+}
+
+/// 聚合函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggFunc {
+    /// 行数 count(*) 统计全部行，count(列) 只统计非空值
+    Count,
+    /// 求和
+    Sum,
+    /// 平均值
+    Avg,
+    /// 最小值 数字按数值比较，其余按字符串比较
+    Min,
+    /// 最大值
+    Max,
+    /// 不同值个数
+    Distinct,
+}
+
+impl FromStr for AggFunc {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "count" => Ok(AggFunc::Count),
+            "sum" => Ok(AggFunc::Sum),
+            "avg" | "mean" => Ok(AggFunc::Avg),
+            "min" => Ok(AggFunc::Min),
+            "max" => Ok(AggFunc::Max),
+            "distinct" | "count_distinct" => Ok(AggFunc::Distinct),
+            _ => Err(anyhow::anyhow!("Invalid aggregate function: {}", s)),
+        }
+    }
+}
+
+impl From<AggFunc> for &'static str {
+    fn from(value: AggFunc) -> Self {
+        match value {
+            AggFunc::Count => "count",
+            AggFunc::Sum => "sum",
+            AggFunc::Avg => "avg",
+            AggFunc::Min => "min",
+            AggFunc::Max => "max",
+            AggFunc::Distinct => "distinct",
+        }
+    }
+}
+
+impl Display for AggFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&'static str>::into(*self))
+    }
+}
+
+/// 聚合表达式 如 avg(Age) as mean_age
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregate {
+    /// 聚合函数
+    pub func: AggFunc,
+    /// 聚合的列 count(*) 时为None
+    pub column: Option<String>,
+    /// 输出列名 默认为表达式本身
+    pub alias: Option<String>,
+}
+
+impl Aggregate {
+    /// 输出列名
+    pub fn label(&self) -> String {
+        match &self.alias {
+            Some(alias) => alias.clone(),
+            None => format!("{}({})", self.func, self.column.as_deref().unwrap_or("*")),
+        }
+    }
+}
+
+/// 输出格式 指向 process::format 中注册的格式实现
+#[derive(Clone, Copy)]
+pub struct OutputFormat(pub(crate) &'static dyn Format);
+
+/// 直接调用格式实现的方法 如 extension、writer、reader
+impl Deref for OutputFormat {
+    type Target = dyn Format;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+/// 格式名相同即为同一格式
+impl PartialEq for OutputFormat {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.name() == other.0.name()
+    }
+}
+
+impl std::fmt::Debug for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OutputFormat({})", self.0.name())
+    }
 }
 
 /// 实现将OutputFormat转换为&'static str
 impl From<OutputFormat> for &'static str {
     fn from(value: OutputFormat) -> Self {
-        match value {
-            OutputFormat::Json => "json",
-            OutputFormat::Yaml => "yaml",
-            OutputFormat::Toml => "toml",
-        }
+        value.0.name()
     }
 }
-/// 实现将&str 转换为OutputFormat
+/// 实现将&str 转换为OutputFormat 按格式名或别名查找
 impl FromStr for OutputFormat {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.to_lowercase();
-        match s.as_str() {
-            "json" => Ok(OutputFormat::Json),
-            "yaml" => Ok(OutputFormat::Yaml),
-            "toml" => Ok(OutputFormat::Toml),
-            _ => Err(anyhow::anyhow!("Invalid Format")),
-        }
+        find_format(&s.to_lowercase())
+            .map(OutputFormat)
+            .ok_or_else(|| {
+                let names: Vec<&str> = formats().map(|f| f.name()).collect();
+                anyhow::anyhow!("Invalid Format: {}, supported: {}", s, names.join(", "))
+            })
     }
 }
 /// 实现Display Trait 方便在输出时使用
@@ -79,7 +725,286 @@ impl Display for OutputFormat {
     }
 }
 
+/// SQL方言 决定标识符引号、字面量和列类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    /// PostgreSQL
+    Postgres,
+    /// MySQL
+    Mysql,
+    /// SQLite
+    Sqlite,
+}
+
+impl FromStr for SqlDialect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "postgres" | "postgresql" | "pg" => Ok(SqlDialect::Postgres),
+            "mysql" | "mariadb" => Ok(SqlDialect::Mysql),
+            "sqlite" => Ok(SqlDialect::Sqlite),
+            _ => Err(anyhow::anyhow!("Invalid SqlDialect")),
+        }
+    }
+}
+
+impl From<SqlDialect> for &'static str {
+    fn from(value: SqlDialect) -> Self {
+        match value {
+            SqlDialect::Postgres => "postgres",
+            SqlDialect::Mysql => "mysql",
+            SqlDialect::Sqlite => "sqlite",
+        }
+    }
+}
+
+impl Display for SqlDialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&'static str>::into(*self))
+    }
+}
+
+/// 输入编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvEncoding {
+    /// 根据BOM和内容自动检测
+    Auto,
+    /// 指定的编码
+    Known(&'static Encoding),
+}
+
+impl FromStr for CsvEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(CsvEncoding::Auto),
+            label => Encoding::for_label(label.as_bytes())
+                .map(CsvEncoding::Known)
+                .ok_or_else(|| anyhow::anyhow!("Invalid Encoding: {}", s)),
+        }
+    }
+}
+
+impl Display for CsvEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CsvEncoding::Auto => write!(f, "auto"),
+            CsvEncoding::Known(encoding) => write!(f, "{}", encoding.name()),
+        }
+    }
+}
+
+/// 去除空白的方式
+#[derive(Debug, Clone, Copy)]
+pub enum CsvTrim {
+    /// 不处理
+    None,
+    /// 只处理Header
+    Headers,
+    /// 只处理字段
+    Fields,
+    /// 全部处理
+    All,
+}
+
+impl FromStr for CsvTrim {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(CsvTrim::None),
+            "headers" => Ok(CsvTrim::Headers),
+            "fields" => Ok(CsvTrim::Fields),
+            "all" => Ok(CsvTrim::All),
+            _ => Err(anyhow::anyhow!("Invalid Trim")),
+        }
+    }
+}
+
+impl From<CsvTrim> for csv::Trim {
+    fn from(value: CsvTrim) -> Self {
+        match value {
+            CsvTrim::None => csv::Trim::None,
+            CsvTrim::Headers => csv::Trim::Headers,
+            CsvTrim::Fields => csv::Trim::Fields,
+            CsvTrim::All => csv::Trim::All,
+        }
+    }
+}
+
+/// 列类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// 空值 整列都为空时使用
+    Null,
+    /// 布尔值
+    Bool,
+    /// 整数
+    Int,
+    /// 浮点数
+    Float,
+    /// ISO-8601 日期
+    Date,
+    /// ISO-8601 时间
+    Time,
+    /// ISO-8601 日期时间
+    DateTime,
+    /// 字符串
+    String,
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "null" => Ok(ColumnType::Null),
+            "bool" | "boolean" => Ok(ColumnType::Bool),
+            "int" | "integer" => Ok(ColumnType::Int),
+            "float" | "number" => Ok(ColumnType::Float),
+            "date" => Ok(ColumnType::Date),
+            "time" => Ok(ColumnType::Time),
+            "datetime" => Ok(ColumnType::DateTime),
+            "string" | "str" => Ok(ColumnType::String),
+            _ => Err(anyhow::anyhow!("Invalid ColumnType")),
+        }
+    }
+}
+
+impl From<ColumnType> for &'static str {
+    fn from(value: ColumnType) -> Self {
+        match value {
+            ColumnType::Null => "null",
+            ColumnType::Bool => "bool",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Date => "date",
+            ColumnType::Time => "time",
+            ColumnType::DateTime => "datetime",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&'static str>::into(*self))
+    }
+}
+
 // 转换输出格式
 fn parse_output_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse::<OutputFormat>()
 }
+
+// 转换列类型 格式为 列名:类型
+fn parse_column_type(s: &str) -> Result<(String, ColumnType)> {
+    let (column, ty) = s
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Expected column:type, got {}", s))?;
+    Ok((column.to_string(), ty.parse()?))
+}
+
+// 转换重命名 格式为 旧列名=新列名
+fn parse_rename(s: &str) -> Result<(String, String)> {
+    let (old, new) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Expected old=new, got {}", s))?;
+    Ok((old.to_string(), new.to_string()))
+}
+
+// 转换表 格式为 表名=文件 或者 文件
+fn parse_table(s: &str) -> Result<(String, String)> {
+    let (name, path) = match s.split_once('=') {
+        Some((name, path)) => (name.to_string(), path),
+        None => {
+            let stem = Path::new(s)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .filter(|stem| *stem != "-")
+                .ok_or_else(|| anyhow::anyhow!("Expected name=file, got {}", s))?;
+            (stem.to_string(), s)
+        }
+    };
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("Missing table name in {}", s));
+    }
+    Ok((name, validate_file(path)?))
+}
+
+// 转换聚合表达式 格式为 函数(列) [as 别名]
+fn parse_aggregate(s: &str) -> Result<Aggregate> {
+    let s = s.trim();
+    let (expr, alias) = match s.rfind(" as ") {
+        Some(idx) => (s[..idx].trim_end(), Some(s[idx + 4..].trim().to_string())),
+        None => (s, None),
+    };
+    let (func, column) = expr
+        .strip_suffix(')')
+        .and_then(|e| e.split_once('('))
+        .ok_or_else(|| anyhow::anyhow!("Expected func(column), got {}", s))?;
+    let func: AggFunc = func.trim().parse()?;
+    let column = match column.trim() {
+        "*" if func == AggFunc::Count => None,
+        "*" => return Err(anyhow::anyhow!("Only count supports *, got {}", s)),
+        "" => return Err(anyhow::anyhow!("Missing column in {}", s)),
+        column => Some(column.to_string()),
+    };
+    Ok(Aggregate {
+        func,
+        column,
+        alias: alias.filter(|a| !a.is_empty()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rename() {
+        assert_eq!(
+            parse_rename("Kit Number=kit").unwrap(),
+            ("Kit Number".to_string(), "kit".to_string())
+        );
+        assert!(parse_rename("Name").is_err());
+    }
+
+    #[test]
+    fn test_parse_column_type() {
+        let (column, ty) = parse_column_type("Kit Number:int").unwrap();
+        assert_eq!(column, "Kit Number");
+        assert_eq!(ty, ColumnType::Int);
+        assert!(parse_column_type("Age").is_err());
+        assert!(parse_column_type("Age:decimal").is_err());
+    }
+
+    #[test]
+    fn test_parse_aggregate() {
+        let agg = parse_aggregate("count(*)").unwrap();
+        assert_eq!(agg.column, None);
+        assert_eq!(agg.label(), "count(*)");
+
+        let agg = parse_aggregate("AVG(Kit Number) as kit").unwrap();
+        assert_eq!(agg.func, AggFunc::Avg);
+        assert_eq!(agg.column.as_deref(), Some("Kit Number"));
+        assert_eq!(agg.label(), "kit");
+
+        assert!(parse_aggregate("sum(*)").is_err());
+        assert!(parse_aggregate("median(Age)").is_err());
+        assert!(parse_aggregate("Age").is_err());
+    }
+
+    #[test]
+    fn test_parse_table() {
+        assert_eq!(
+            parse_table("players=assets/juventus.csv").unwrap(),
+            ("players".to_string(), "assets/juventus.csv".to_string())
+        );
+        assert_eq!(parse_table("assets/juventus.csv").unwrap().0, "juventus");
+        assert!(parse_table("players=missing.csv").is_err());
+        assert!(parse_table("=assets/juventus.csv").is_err());
+    }
+}
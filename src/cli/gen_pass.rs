@@ -1,17 +1,58 @@
-use clap::Parser;
+use std::{fmt::Display, str::FromStr};
 
-use crate::process_gen_pass;
+use clap::{builder::RangedU64ValueParser, Parser};
 
-use super::CmdExecutor;
+use crate::{
+    process_derive_pass, process_gen_pass, process_gen_passphrase, process_pass_check,
+    CheckOptions, DeriveOptions, PassphraseOptions, PasswordPolicy,
+};
+
+use super::{exit_with_status, validate_file, CmdExecutor, ReportFormat};
 use anyhow::Result;
 use zxcvbn::zxcvbn;
 
-/// 生成密码选项
+/// 生成密码选项 也可以用check子命令检查已有的密码
 #[derive(Debug, Clone, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
-    /// 密码长度
+    /// 子命令
+    #[command(subcommand)]
+    pub command: Option<GenPassSubCommand>,
+    /// 密码策略选项
+    #[command(flatten)]
+    pub policy_opts: PasswordPolicyOpts,
+    /// 生成由N个单词组成的口令 默认使用EFF大词表
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        conflicts_with_all = [
+            "length", "no_number", "no_special", "no_upper", "no_lower",
+            "min_number", "min_special", "min_upper", "min_lower",
+            "allowed", "forbidden", "allow_ambiguous", "max_repeat",
+            "min_score", "min_entropy", "policy_file",
+        ])]
+    pub words: Option<usize>,
+    /// 自定义词表文件 每行一个词，也支持EFF词表的格式
+    #[arg(long, value_parser = validate_file, requires = "words")]
+    pub wordlist: Option<String>,
+    /// 单词之间的分隔符
+    #[arg(long, default_value = "-", requires = "words")]
+    pub separator: String,
+    /// 每个单词首字母大写
+    #[arg(long, default_value_t = false, requires = "words")]
+    pub capitalize: bool,
+    /// 在随机一个单词后加上一位数字
+    #[arg(long, default_value_t = false, requires = "words")]
+    pub add_number: bool,
+    /// 在随机一个单词后加上一个特殊字符
+    #[arg(long, default_value_t = false, requires = "words")]
+    pub add_special: bool,
+}
+
+/// 密码策略选项 生成和检查密码时共用
+#[derive(Debug, Clone, Parser)]
+pub struct PasswordPolicyOpts {
+    /// 密码长度 检查密码时为最小长度
     #[arg(long, default_value_t = 16)]
-    pub length: u8,
+    pub length: usize,
     /// 是否不包含数字
     #[arg(long, default_value_t = false)]
     pub no_number: bool,
@@ -24,21 +65,263 @@ pub struct GenPassOpts {
     /// 是否不包含小写字母
     #[arg(long, default_value_t = false)]
     pub no_lower: bool,
+    /// 数字的最少个数
+    #[arg(long, default_value_t = 1)]
+    pub min_number: usize,
+    /// 特殊字符的最少个数
+    #[arg(long, default_value_t = 1)]
+    pub min_special: usize,
+    /// 大写字母的最少个数
+    #[arg(long, default_value_t = 1)]
+    pub min_upper: usize,
+    /// 小写字母的最少个数
+    #[arg(long, default_value_t = 1)]
+    pub min_lower: usize,
+    /// 只使用这些字符 其中非字母数字的字符作为特殊字符
+    #[arg(long)]
+    pub allowed: Option<String>,
+    /// 不使用这些字符
+    #[arg(long, default_value = "")]
+    pub forbidden: String,
+    /// 允许容易混淆的字符 如 0O1lI
+    #[arg(long, default_value_t = false)]
+    pub allow_ambiguous: bool,
+    /// 同一字符最多连续出现的次数
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_repeat: Option<usize>,
+    /// 最低的zxcvbn评分 0-4，未达到时重新生成
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=4))]
+    pub min_score: Option<u8>,
+    /// zxcvbn估算的最低熵 单位为bit，未达到时重新生成
+    #[arg(long)]
+    pub min_entropy: Option<f64>,
+    /// 本地的HIBP泄露密码SHA-1哈希文件 按哈希排序，或按前缀下载的目录，
+    /// 出现在其中的密码不合格，生成时重新生成
+    #[arg(long, value_parser = validate_file)]
+    pub pwned_file: Option<String>,
+    /// 密码策略文件 Toml格式，每个策略是一个表，指定后忽略上面的选项 --pwned-file除外
+    #[arg(long, value_parser = validate_file)]
+    pub policy_file: Option<String>,
+    /// 使用的策略名
+    #[arg(long, default_value = "default", requires = "policy_file")]
+    pub policy: String,
+}
+
+impl PasswordPolicyOpts {
+    /// 从策略文件加载 或由命令行选项组成密码策略
+    pub fn password_policy(&self) -> Result<PasswordPolicy> {
+        if let Some(path) = &self.policy_file {
+            let mut policy = PasswordPolicy::load(path, &self.policy)?;
+            // 泄露密码文件的位置因机器而异 命令行指定时优先
+            if self.pwned_file.is_some() {
+                policy.pwned_file = self.pwned_file.clone();
+            }
+            return Ok(policy);
+        }
+        Ok(PasswordPolicy {
+            length: self.length,
+            no_number: self.no_number,
+            no_special: self.no_special,
+            no_upper: self.no_upper,
+            no_lower: self.no_lower,
+            min_number: self.min_number,
+            min_special: self.min_special,
+            min_upper: self.min_upper,
+            min_lower: self.min_lower,
+            allowed: self.allowed.clone(),
+            forbidden: self.forbidden.clone(),
+            exclude_ambiguous: !self.allow_ambiguous,
+            max_repeat: self.max_repeat,
+            min_score: self.min_score,
+            min_entropy: self.min_entropy,
+            pwned_file: self.pwned_file.clone(),
+        })
+    }
 }
 
 /// 实现执行逻辑
 impl CmdExecutor for GenPassOpts {
     async fn execute(self) -> Result<()> {
-        let password = process_gen_pass(
-            self.length,
-            self.no_number,
-            self.no_special,
-            self.no_upper,
-            self.no_lower,
-        )?;
+        if let Some(command) = self.command {
+            return command.execute().await;
+        }
+        if let Some(words) = self.words {
+            let passphrase = process_gen_passphrase(&PassphraseOptions {
+                words,
+                wordlist: self.wordlist.as_deref(),
+                separator: &self.separator,
+                capitalize: self.capitalize,
+                add_number: self.add_number,
+                add_special: self.add_special,
+                pwned_file: self.policy_opts.pwned_file.as_deref(),
+            })?;
+            println!("Generated Passphrase: {}", passphrase.phrase);
+            println!("Entropy: {:.2} bits", passphrase.entropy);
+            let entropy = zxcvbn(&passphrase.phrase, &[])?;
+            println!("Password Strength: {}", entropy.score());
+            return Ok(());
+        }
+        let password = process_gen_pass(&self.policy_opts.password_policy()?)?;
         println!("Generated Password: {}", password);
         let entropy = zxcvbn(&password, &[])?;
         println!("Password Strength: {}", entropy.score());
         Ok(())
     }
 }
+
+/// 密码子命令
+#[derive(Debug, Clone, Parser)]
+#[enum_dispatch::enum_dispatch(CmdExecutor)]
+pub enum GenPassSubCommand {
+    /// 按密码策略和zxcvbn检查已有的密码
+    Check(GenPassCheckOpts),
+    /// 由主密码为站点派生固定的密码 无需保存
+    Derive(GenPassDeriveOpts),
+}
+
+/// 检查密码选项
+#[derive(Debug, Clone, Parser)]
+pub struct GenPassCheckOpts {
+    /// 输入文件 每行一个密码，默认从标准输入读取，终端中输入时不回显
+    #[arg(short, long, value_parser = validate_file, default_value = "-")]
+    pub input: String,
+    /// 输出路径 默认为标准输出
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// 报告格式 支持text和json
+    #[arg(long, default_value = "text")]
+    pub format: ReportFormat,
+    /// 把容易混淆的字符 如 0O1lI 算作违规，默认不检查
+    #[arg(long, default_value_t = false, conflicts_with = "allow_ambiguous")]
+    pub check_ambiguous: bool,
+    /// 密码策略选项
+    #[command(flatten)]
+    pub policy_opts: PasswordPolicyOpts,
+}
+
+impl CmdExecutor for GenPassCheckOpts {
+    async fn execute(self) -> Result<()> {
+        let opts = CheckOptions {
+            input: &self.input,
+            output: &self.output,
+            format: self.format,
+            check_ambiguous: self.check_ambiguous,
+        };
+        let report = self
+            .policy_opts
+            .password_policy()
+            .and_then(|policy| process_pass_check(&policy, &opts));
+        exit_with_status(report, |report| report.failed == 0)
+    }
+}
+
+/// 派生密码选项
+#[derive(Debug, Clone, Parser)]
+pub struct GenPassDeriveOpts {
+    /// 站点 不区分大小写
+    #[arg(long)]
+    pub site: String,
+    /// 登录名
+    #[arg(long, default_value = "")]
+    pub login: String,
+    /// 计数器 需要更换密码时加一
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub counter: u32,
+    /// 密钥派生函数 支持argon2id和scrypt
+    #[arg(long, default_value = "argon2id")]
+    pub kdf: Kdf,
+    /// 读取主密码的环境变量 未设置时在终端中输入，不回显
+    #[arg(long, default_value = "RCLI_MASTER_PASSWORD")]
+    pub master_env: String,
+    /// 密码策略选项
+    #[command(flatten)]
+    pub policy_opts: PasswordPolicyOpts,
+}
+
+impl CmdExecutor for GenPassDeriveOpts {
+    async fn execute(self) -> Result<()> {
+        let master = match std::env::var(&self.master_env) {
+            Ok(master) => master,
+            Err(_) => rpassword::prompt_password("Master password: ")?,
+        };
+        let password = process_derive_pass(
+            &master,
+            &DeriveOptions {
+                site: &self.site,
+                login: &self.login,
+                counter: self.counter,
+                kdf: self.kdf,
+            },
+            &self.policy_opts.password_policy()?,
+        )?;
+        println!("Derived Password: {}", password);
+        let entropy = zxcvbn(&password, &[])?;
+        println!("Password Strength: {}", entropy.score());
+        Ok(())
+    }
+}
+
+/// 密钥派生函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    /// Argon2id 64MiB内存
+    Argon2id,
+    /// scrypt 32MiB内存
+    Scrypt,
+}
+
+impl FromStr for Kdf {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "argon2id" => Ok(Kdf::Argon2id),
+            "scrypt" => Ok(Kdf::Scrypt),
+            _ => Err(anyhow::anyhow!("Invalid Kdf")),
+        }
+    }
+}
+
+impl From<Kdf> for &'static str {
+    fn from(value: Kdf) -> Self {
+        match value {
+            Kdf::Argon2id => "argon2id",
+            Kdf::Scrypt => "scrypt",
+        }
+    }
+}
+
+impl Display for Kdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&'static str>::into(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words_conflicts_with_policy_options() {
+        for args in [
+            ["--min-entropy", "60"],
+            ["--min-score", "3"],
+            ["--max-repeat", "2"],
+            ["--forbidden", "x"],
+        ] {
+            let mut argv = vec!["gen-pass", "--words", "5"];
+            argv.extend(args);
+            assert!(GenPassOpts::try_parse_from(argv).is_err());
+        }
+        assert!(GenPassOpts::try_parse_from(["gen-pass", "--words", "5", "--capitalize"]).is_ok());
+        // 口令也要检查是否已泄露
+        assert!(GenPassOpts::try_parse_from([
+            "gen-pass",
+            "--words",
+            "5",
+            "--pwned-file",
+            "fixtures/pwned/range"
+        ])
+        .is_ok());
+    }
+}
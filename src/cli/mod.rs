@@ -2,6 +2,7 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use std::path::{Path, PathBuf};
 mod b64;
+mod convert;
 mod csv;
 mod gen_pass;
 mod http;
@@ -10,7 +11,7 @@ mod text;
 
 use crate::CmdExecutor;
 
-pub use {b64::*, csv::*, gen_pass::*, http::*, jwt::*, text::*};
+pub use {b64::*, convert::*, csv::*, gen_pass::*, http::*, jwt::*, text::*};
 
 /// 应用程序命令行
 #[derive(Debug, Clone, Parser)]
@@ -28,11 +29,15 @@ pub struct Opts {
 }
 
 /// 子命令枚举 对应SubCommand
+/// 命令行只解析一次 不需要为了变体大小而装箱
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Subcommand)]
 #[enum_dispatch::enum_dispatch(CmdExecutor)]
 pub enum SubCommand {
-    /// 将CSV转换为其他格式，如Json,Yaml
-    Csv(CsvOpts),
+    /// 将CSV转换为其他格式，如Json,Yaml，以及统计等处理
+    Csv(CsvCommand),
+    /// 将Json,Yaml,Toml等格式转换为CSV
+    Convert(ConvertOpts),
     /// 生成随机密码
     GenPass(GenPassOpts),
     /// Base64编码解码
@@ -49,6 +54,18 @@ pub enum SubCommand {
     Jwt(JwtSubCommand),
 }
 
+/// 按检查结果退出 与diff命令一致: 0表示通过，1表示未通过，2表示出错
+pub fn exit_with_status<T>(result: Result<T>, passed: impl FnOnce(&T) -> bool) -> Result<()> {
+    match result {
+        Ok(value) if passed(&value) => Ok(()),
+        Ok(_) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(2)
+        }
+    }
+}
+
 /// 验证输入文件
 pub fn validate_file(input: &str) -> Result<String> {
     if Path::new(input).exists() || input == "-" {
@@ -68,6 +85,25 @@ pub fn validate_path(input: &str) -> Result<PathBuf> {
     }
 }
 
+/// 转换单个ASCII字符 csv只支持单字节的分隔符
+pub fn parse_ascii_char(s: &str) -> Result<char> {
+    let c = match s {
+        "\\t" | "tab" => '\t',
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(anyhow::anyhow!("Must be a single character")),
+            }
+        }
+    };
+    if c.is_ascii() {
+        Ok(c)
+    } else {
+        Err(anyhow::anyhow!("Must be an ASCII character"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,4 +114,12 @@ mod tests {
         assert!(validate_file("not_exists").is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_parse_ascii_char() {
+        assert_eq!(parse_ascii_char(";").unwrap(), ';');
+        assert_eq!(parse_ascii_char("\\t").unwrap(), '\t');
+        assert!(parse_ascii_char(";;").is_err());
+        assert!(parse_ascii_char("；").is_err());
+    }
 }
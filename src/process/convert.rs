@@ -0,0 +1,270 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::{ConvertOpts, HeaderMode, OutputFormat},
+    get_writer,
+};
+
+use super::{
+    encoding::bom_writer,
+    record_reader::{detect_format, read_records},
+    record_writer::{record_writer, RecordWriter},
+};
+
+/// 一行扁平化之后的数据 (列名, 值) 值都是标量，写入CSV时再转换为文本
+type FlatRow = Vec<(String, Value)>;
+
+/// 将Json,Yaml,Toml,Ndjson转换为CSV或Excel工作簿
+pub fn process_convert(opts: &ConvertOpts, output: &str) -> Result<()> {
+    let format = match opts.from {
+        Some(format) => format,
+        None => detect_format(&opts.input)?,
+    };
+    let records = read_records(format, &opts.input)?;
+    let flatten = |record: Result<Value>| -> Result<FlatRow> {
+        Ok(flatten_record(
+            &record?,
+            &opts.key_separator,
+            opts.array_separator.as_deref(),
+        ))
+    };
+
+    let mut writer = TableSink::new(opts, output)?;
+
+    match opts.headers {
+        // 以第一条记录的列为准 可以流式处理
+        HeaderMode::First => {
+            let mut records = records.map(flatten);
+            let Some(first) = records.next().transpose()? else {
+                return writer.finish();
+            };
+            let headers: Vec<String> = first.iter().map(|(k, _)| k.clone()).collect();
+            writer.write_headers(&headers)?;
+            let mut dropped = HashSet::new();
+            for row in std::iter::once(Ok(first)).chain(records) {
+                let row = row?;
+                for (key, _) in &row {
+                    if !headers.contains(key) && dropped.insert(key.clone()) {
+                        tracing::warn!("Column {} is not in the first record, dropped", key);
+                    }
+                }
+                writer.write_row(&headers, align_row(&headers, row))?;
+            }
+        }
+        // 所有记录的列的并集 需要先读取全部记录
+        HeaderMode::Union => {
+            let rows = records.map(flatten).collect::<Result<Vec<_>>>()?;
+            let mut headers = vec![];
+            let mut seen = HashSet::new();
+            for (key, _) in rows.iter().flatten() {
+                if seen.insert(key.as_str()) {
+                    headers.push(key.clone());
+                }
+            }
+            if !headers.is_empty() {
+                writer.write_headers(&headers)?;
+            }
+            for row in rows {
+                writer.write_row(&headers, align_row(&headers, row))?;
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+/// 转换的输出 CSV写入文本单元格，Excel保留值的类型
+enum TableSink {
+    Csv(Box<csv::Writer<Box<dyn Write>>>),
+    Records(Box<dyn RecordWriter>),
+}
+
+impl TableSink {
+    fn new(opts: &ConvertOpts, output: &str) -> Result<Self> {
+        Ok(match opts.to {
+            format if format == OutputFormat::CSV => {
+                let writer = if opts.bom {
+                    bom_writer(output)?
+                } else {
+                    get_writer(output)?
+                };
+                TableSink::Csv(Box::new(
+                    csv::WriterBuilder::new()
+                        .delimiter(opts.delimiter as u8)
+                        .from_writer(writer),
+                ))
+            }
+            format => TableSink::Records(record_writer(format, output)?),
+        })
+    }
+
+    // 写入列名 Excel由第一条记录决定列名
+    fn write_headers(&mut self, headers: &[String]) -> Result<()> {
+        if let TableSink::Csv(writer) = self {
+            writer.write_record(headers)?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, headers: &[String], row: Vec<Value>) -> Result<()> {
+        match self {
+            TableSink::Csv(writer) => writer.write_record(row.iter().map(scalar_cell))?,
+            TableSink::Records(writer) => {
+                let record: Map<String, Value> = headers.iter().cloned().zip(row).collect();
+                writer.write(&Value::Object(record))?
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self {
+            TableSink::Csv(writer) => writer.flush()?,
+            TableSink::Records(writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
+
+/// 将记录扁平化 嵌套对象用key_sep连接列名
+/// 数组默认展开为 tags[0] 形式的列，指定array_sep时标量数组合并为一个单元格
+pub fn flatten_record(record: &Value, key_sep: &str, array_sep: Option<&str>) -> FlatRow {
+    let mut row = vec![];
+    match record {
+        Value::Object(_) => flatten_value("", record, key_sep, array_sep, &mut row),
+        // 非对象的记录只有一列
+        _ => flatten_value("value", record, key_sep, array_sep, &mut row),
+    }
+    row
+}
+
+fn flatten_value(
+    prefix: &str,
+    value: &Value,
+    key_sep: &str,
+    array_sep: Option<&str>,
+    row: &mut FlatRow,
+) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}{}{}", prefix, key_sep, key)
+                };
+                flatten_value(&key, value, key_sep, array_sep, row);
+            }
+        }
+        Value::Array(items) => match array_sep {
+            Some(sep) if items.iter().all(|v| !v.is_object() && !v.is_array()) => {
+                let cell = items.iter().map(scalar_cell).collect::<Vec<_>>();
+                row.push((prefix.to_string(), Value::String(cell.join(sep))));
+            }
+            _ => {
+                for (i, value) in items.iter().enumerate() {
+                    let key = format!("{}[{}]", prefix, i);
+                    flatten_value(&key, value, key_sep, array_sep, row);
+                }
+            }
+        },
+        _ => row.push((prefix.to_string(), value.clone())),
+    }
+}
+
+// 标量转换为单元格 null为空
+fn scalar_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+// 按列名顺序排列单元格 缺失的列为空
+fn align_row<T: Default>(headers: &[String], row: Vec<(String, T)>) -> Vec<T> {
+    let mut cells: HashMap<String, T> = row.into_iter().collect();
+    headers
+        .iter()
+        .map(|h| cells.remove(h).unwrap_or_default())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_flatten_record() {
+        let record = json!({
+            "name": "Alice",
+            "age": 30,
+            "address": {"city": "Torino", "zip": null},
+            "tags": ["a", "b"],
+            "items": [{"id": 1}]
+        });
+        let row = flatten_record(&record, ".", None);
+        let keys: Vec<_> = row.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "name",
+                "age",
+                "address.city",
+                "address.zip",
+                "tags[0]",
+                "tags[1]",
+                "items[0].id"
+            ]
+        );
+        // 保留值的类型 写入CSV时null为空
+        assert_eq!(row[3].1, Value::Null);
+        assert_eq!(scalar_cell(&row[3].1), "");
+
+        let row = flatten_record(&record, "_", Some(";"));
+        assert!(row.contains(&("tags".to_string(), json!("a;b"))));
+        assert!(row.contains(&("address_city".to_string(), json!("Torino"))));
+        assert!(row.contains(&("items[0]_id".to_string(), json!(1))));
+    }
+
+    #[test]
+    fn test_align_row() {
+        let headers = vec!["a".to_string(), "b".to_string()];
+        let row = vec![("b".to_string(), "2".to_string())];
+        assert_eq!(align_row(&headers, row), vec!["", "2"]);
+    }
+
+    #[test]
+    fn test_convert_to_xlsx_keeps_types() -> Result<()> {
+        use clap::Parser;
+
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("input.json");
+        let output = dir.path().join("output.xlsx");
+        std::fs::write(
+            &input,
+            r#"[{"name": "Alice", "age": 30, "tags": ["a", "b"]}, {"name": "Bob", "age": 41.5}]"#,
+        )?;
+        let opts = ConvertOpts::parse_from([
+            "convert",
+            "-i",
+            input.to_str().unwrap(),
+            "--to",
+            "xlsx",
+            "--array-separator",
+            ";",
+        ]);
+        process_convert(&opts, output.to_str().unwrap())?;
+        let csv = String::from_utf8(crate::read_sheet(output.to_str().unwrap(), None, None)?)?;
+        assert_eq!(csv, "name,age,tags\nAlice,30,a;b\nBob,41.5,\n");
+        assert!(ConvertOpts::try_parse_from(["convert", "-i", "-", "--to", "yaml"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::{AggFunc, Aggregate, CsvAggOpts},
+    get_writer,
+};
+
+use super::{
+    csv_filter::Filter,
+    csv_infer::is_null,
+    csv_reader::{column_index, open_csv},
+    record_writer::{cell_text, record_writer},
+};
+
+/// 聚合结果 列名和每一行
+pub struct AggTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Value>,
+}
+
+/// 分组聚合并输出 没有指定格式时输出CSV
+pub fn process_csv_agg(opts: &CsvAggOpts) -> Result<()> {
+    let table = aggregate_csv(opts)?;
+    match opts.format {
+        Some(format) => {
+            let mut writer = record_writer(format, &opts.output)?;
+            for row in &table.rows {
+                writer.write(row)?;
+            }
+            writer.finish()
+        }
+        None => {
+            let mut writer = csv::Writer::from_writer(get_writer(&opts.output)?);
+            writer.write_record(&table.headers)?;
+            for row in &table.rows {
+                writer.write_record(table.headers.iter().map(|h| cell_text(row.get(h))))?;
+            }
+            Ok(writer.flush()?)
+        }
+    }
+}
+
+/// 按分组列聚合 分组和透视列的顺序与首次出现的顺序一致
+pub fn aggregate_csv(opts: &CsvAggOpts) -> Result<AggTable> {
+    let (mut reader, headers) = open_csv(&opts.input, &opts.reader)?;
+    let filter = opts
+        .filter
+        .as_deref()
+        .map(|f| Filter::parse(f, &headers))
+        .transpose()?;
+    let group_by = opts
+        .group_by
+        .iter()
+        .map(|c| column_index(&headers, c))
+        .collect::<Result<Vec<_>>>()?;
+    let pivot = opts
+        .pivot
+        .as_deref()
+        .map(|c| column_index(&headers, c))
+        .transpose()?;
+    let aggs = if opts.agg.is_empty() {
+        vec![Aggregate {
+            func: AggFunc::Count,
+            column: None,
+            alias: None,
+        }]
+    } else {
+        opts.agg.clone()
+    };
+    let agg_columns = aggs
+        .iter()
+        .map(|a| a.column.as_deref().map(|c| column_index(&headers, c)))
+        .map(Option::transpose)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut groups = Keys::default();
+    let mut pivots = Keys::default();
+    let mut cells: HashMap<(usize, usize), Vec<Accumulator>> = HashMap::new();
+    for row in reader.records() {
+        let row = row?;
+        if filter.as_ref().is_some_and(|f| !f.matches(&row)) {
+            continue;
+        }
+        let key = group_by
+            .iter()
+            .map(|&i| row.get(i).unwrap_or_default().to_string())
+            .collect();
+        let group = groups.insert(key);
+        let pivot = pivots.insert(
+            pivot
+                .map(|i| vec![row.get(i).unwrap_or_default().to_string()])
+                .unwrap_or_default(),
+        );
+        let line = row.position().map(|p| p.line()).unwrap_or_default();
+        let accumulators = cells
+            .entry((group, pivot))
+            .or_insert_with(|| aggs.iter().map(|a| Accumulator::new(a.func)).collect());
+        for ((acc, column), agg) in accumulators.iter_mut().zip(&agg_columns).zip(&aggs) {
+            acc.push(column.map(|i| row.get(i).unwrap_or_default()))
+                .map_err(|e| anyhow::anyhow!("line {}, {}: {}", line, agg.label(), e))?;
+        }
+    }
+
+    // 输出列 分组列在前，透视时每个透视值对应一组聚合列
+    let mut out_headers: Vec<String> = group_by.iter().map(|&i| headers[i].to_string()).collect();
+    let agg_headers = |value: Option<&str>| -> Vec<String> {
+        aggs.iter()
+            .map(|a| match value {
+                None => a.label(),
+                Some(value) if aggs.len() == 1 => value.to_string(),
+                Some(value) => format!("{}_{}", value, a.label()),
+            })
+            .collect()
+    };
+    if pivot.is_some() {
+        for value in &pivots.keys {
+            let value = if value[0].is_empty() {
+                "null"
+            } else {
+                &value[0]
+            };
+            out_headers.extend(agg_headers(Some(value)));
+        }
+    } else {
+        out_headers.extend(agg_headers(None));
+    }
+    let mut seen = HashSet::new();
+    if let Some(dup) = out_headers.iter().find(|h| !seen.insert(h.as_str())) {
+        return Err(anyhow::anyhow!("Duplicate output column: {}", dup));
+    }
+
+    // 没有分组列时 即使没有数据也输出一行汇总
+    if group_by.is_empty() && groups.keys.is_empty() {
+        groups.insert(vec![]);
+    }
+    let rows = groups
+        .keys
+        .iter()
+        .enumerate()
+        .map(|(group, key)| {
+            let mut values: Vec<Value> = key.iter().map(|k| Value::from(k.as_str())).collect();
+            for pivot in 0..pivots.keys.len().max(1) {
+                match cells.get(&(group, pivot)) {
+                    Some(accumulators) => values.extend(accumulators.iter().map(|a| a.finish())),
+                    // 该组合没有数据 计数为0，其余为空
+                    None => values.extend(aggs.iter().map(|a| Accumulator::new(a.func).finish())),
+                }
+            }
+            let row: Map<String, Value> = out_headers.iter().cloned().zip(values).collect();
+            Value::Object(row)
+        })
+        .collect();
+
+    Ok(AggTable {
+        headers: out_headers,
+        rows,
+    })
+}
+
+/// 按首次出现的顺序给键编号
+#[derive(Default)]
+struct Keys {
+    index: HashMap<Vec<String>, usize>,
+    keys: Vec<Vec<String>>,
+}
+
+impl Keys {
+    fn insert(&mut self, key: Vec<String>) -> usize {
+        if let Some(&idx) = self.index.get(&key) {
+            return idx;
+        }
+        self.keys.push(key.clone());
+        self.index.insert(key, self.keys.len() - 1);
+        self.keys.len() - 1
+    }
+}
+
+/// 单个聚合的累积状态 空值不参与聚合
+enum Accumulator {
+    Count(u64),
+    /// 没有值时为None
+    Sum(Option<Total>),
+    Avg {
+        sum: f64,
+        count: u64,
+    },
+    Min(Option<String>),
+    Max(Option<String>),
+    Distinct(HashSet<String>),
+}
+
+impl Accumulator {
+    fn new(func: AggFunc) -> Self {
+        match func {
+            AggFunc::Count => Self::Count(0),
+            AggFunc::Sum => Self::Sum(None),
+            AggFunc::Avg => Self::Avg { sum: 0.0, count: 0 },
+            AggFunc::Min => Self::Min(None),
+            AggFunc::Max => Self::Max(None),
+            AggFunc::Distinct => Self::Distinct(HashSet::new()),
+        }
+    }
+
+    // cell为None表示count(*)
+    fn push(&mut self, cell: Option<&str>) -> Result<()> {
+        let Some(cell) = cell else {
+            if let Self::Count(n) = self {
+                *n += 1;
+            }
+            return Ok(());
+        };
+        if is_null(cell) {
+            return Ok(());
+        }
+        match self {
+            Self::Count(n) => *n += 1,
+            Self::Sum(total) => {
+                *total = Some(match (*total, cell.parse::<i64>()) {
+                    (None, Ok(n)) => Total::Int(n as i128),
+                    (Some(Total::Int(sum)), Ok(n)) => Total::Int(sum + n as i128),
+                    (total, _) => Total::Float(
+                        total.map(Total::as_f64).unwrap_or_default() + parse_number(cell)?,
+                    ),
+                });
+            }
+            Self::Avg { sum, count } => {
+                *sum += parse_number(cell)?;
+                *count += 1;
+            }
+            Self::Min(min) => {
+                if min.as_deref().is_none_or(|m| compare(cell, m).is_lt()) {
+                    *min = Some(cell.to_string());
+                }
+            }
+            Self::Max(max) => {
+                if max.as_deref().is_none_or(|m| compare(cell, m).is_gt()) {
+                    *max = Some(cell.to_string());
+                }
+            }
+            Self::Distinct(values) => {
+                if !values.contains(cell) {
+                    values.insert(cell.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&self) -> Value {
+        match self {
+            Self::Count(n) => Value::from(*n),
+            Self::Sum(None) => Value::Null,
+            Self::Sum(Some(Total::Int(sum))) => match i64::try_from(*sum) {
+                Ok(sum) => Value::from(sum),
+                Err(_) => Value::from(*sum as f64),
+            },
+            Self::Sum(Some(Total::Float(sum))) => Value::from(*sum),
+            Self::Avg { count: 0, .. } => Value::Null,
+            Self::Avg { sum, count } => Value::from(sum / *count as f64),
+            Self::Min(v) | Self::Max(v) => v.as_deref().map(number_or_string).unwrap_or_default(),
+            Self::Distinct(values) => Value::from(values.len()),
+        }
+    }
+}
+
+/// 求和的结果 全是整数时精确累加，出现小数后改用浮点数
+#[derive(Clone, Copy)]
+enum Total {
+    Int(i128),
+    Float(f64),
+}
+
+impl Total {
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Int(n) => n as f64,
+            Self::Float(f) => f,
+        }
+    }
+}
+
+fn parse_number(cell: &str) -> Result<f64> {
+    cell.parse()
+        .map_err(|_| anyhow::anyhow!("'{}' is not a number", cell))
+}
+
+// 两边都是数字时按数值比较 否则按字符串比较
+fn compare(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+fn number_or_string(cell: &str) -> Value {
+    if let Ok(n) = cell.parse::<i64>() {
+        Value::from(n)
+    } else if let Ok(n) = cell.parse::<f64>() {
+        Value::from(n)
+    } else {
+        Value::from(cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use serde_json::json;
+
+    fn aggregate(args: &[&str]) -> Result<AggTable> {
+        let opts = CsvAggOpts::parse_from(
+            ["agg", "-i", "assets/juventus.csv"]
+                .iter()
+                .chain(args)
+                .copied(),
+        );
+        aggregate_csv(&opts)
+    }
+
+    #[test]
+    fn test_group_by() -> Result<()> {
+        let table = aggregate(&[
+            "--group-by",
+            "Position",
+            "--agg",
+            "count(*),min(Kit Number) as min_kit,avg(Kit Number),distinct(Nationality)",
+        ])?;
+        assert_eq!(
+            table.headers,
+            vec![
+                "Position",
+                "count(*)",
+                "min_kit",
+                "avg(Kit Number)",
+                "distinct(Nationality)"
+            ]
+        );
+        assert_eq!(table.rows.len(), 10);
+        let goalkeeper = &table.rows[0];
+        assert_eq!(goalkeeper["Position"], json!("Goalkeeper"));
+        assert_eq!(goalkeeper["count(*)"], json!(4));
+        assert_eq!(goalkeeper["min_kit"], json!(1));
+
+        let total = aggregate(&["--agg", "count(*),sum(Kit Number)"])?;
+        assert_eq!(total.rows.len(), 1);
+        assert_eq!(total.rows[0]["count(*)"], json!(27));
+        assert!(total.rows[0]["sum(Kit Number)"].is_i64());
+
+        assert!(aggregate(&["--agg", "sum(Name)"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pivot() -> Result<()> {
+        let table = aggregate(&[
+            "--group-by",
+            "Position",
+            "--pivot",
+            "Nationality",
+            "--where",
+            "Position == \"Goalkeeper\"",
+        ])?;
+        assert_eq!(table.rows.len(), 1);
+        let row = &table.rows[0];
+        let total: u64 = table.headers[1..]
+            .iter()
+            .map(|h| row[h].as_u64().unwrap())
+            .sum();
+        assert_eq!(total, 4);
+        assert_eq!(row["Italy"], json!(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_large_integers_exactly() -> Result<()> {
+        // 超过2^53的整数 用浮点数累加会丢失精度
+        let mut sum = Accumulator::new(AggFunc::Sum);
+        for cell in ["9007199254740993", "1", ""] {
+            sum.push(Some(cell))?;
+        }
+        assert_eq!(sum.finish(), json!(9007199254740994i64));
+
+        let mut sum = Accumulator::new(AggFunc::Sum);
+        assert_eq!(sum.finish(), Value::Null);
+        for cell in ["1", "2.5", "3"] {
+            sum.push(Some(cell))?;
+        }
+        assert_eq!(sum.finish(), json!(6.5));
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::Result;
+use csv::StringRecord;
+
+use crate::cli::CsvCatOpts;
+
+use super::{csv_reader::open_csv, row_writer::RowWriter};
+
+/// 拼接多个CSV 先读取所有Header确定输出列，再逐个文件流式输出
+pub fn process_csv_cat(opts: &CsvCatOpts) -> Result<()> {
+    if opts.inputs.iter().filter(|i| *i == "-").count() > 1 {
+        return Err(anyhow::anyhow!("Standard input can only be used once"));
+    }
+
+    // 输出列为所有文件列名的并集 按首次出现的顺序
+    let mut headers: Vec<String> = vec![];
+    let mut readers = vec![];
+    for input in &opts.inputs {
+        let (reader, file_headers) = open_csv(input, &opts.reader)?;
+        let mapping: Vec<usize> = file_headers
+            .iter()
+            .map(|h| match headers.iter().position(|o| o == h) {
+                Some(idx) => idx,
+                None => {
+                    headers.push(h.to_string());
+                    headers.len() - 1
+                }
+            })
+            .collect();
+        readers.push((reader, mapping));
+    }
+
+    let headers = StringRecord::from(headers);
+    let mut writer = RowWriter::new(&opts.output, opts.format, &headers, opts.reader.delimiter)?;
+    for (mut reader, mapping) in readers {
+        for row in reader.records() {
+            let row = row?;
+            // 缺少的列为空
+            let mut out = vec![""; headers.len()];
+            for (cell, &idx) in row.iter().zip(&mapping) {
+                out[idx] = cell;
+            }
+            writer.write(&StringRecord::from(out))?;
+        }
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_cat_aligns_headers() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("cat.csv");
+        let output = output.to_str().unwrap();
+        let opts = CsvCatOpts::parse_from([
+            "cat",
+            "fixtures/csv_diff_old.csv",
+            "fixtures/csv_nations.csv",
+            "-o",
+            output,
+        ]);
+        process_csv_cat(&opts)?;
+        let content = std::fs::read_to_string(output)?;
+
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines[0],
+            "ID,Name,Position,Kit Number,Nationality,Continent"
+        );
+        assert_eq!(lines.len(), 1 + 4 + 4);
+        assert_eq!(lines[1], "1,Wojciech Szczesny,Goalkeeper,1,,");
+        assert_eq!(lines[5], ",,,,Italy,Europe");
+        Ok(())
+    }
+}
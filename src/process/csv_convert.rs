@@ -1,36 +1,297 @@
-use std::collections::HashMap;
+use std::fs::File;
 
 use anyhow::Result;
+use csv::StringRecord;
+use serde_json::Value;
 
-use crate::cli::OutputFormat;
-
-/// 转换数据
-pub fn process_csv(input: &str, output: &str, format: OutputFormat) -> Result<()> {
-    // 读取文件
-    let mut reader = csv::ReaderBuilder::new().from_path(input)?;
-    // 结果集
-    let mut record = vec![];
-    // 获取headers
-    let headers = reader.headers()?.clone();
-    // 遍历结果
-    for row in reader.records() {
-        // 使用迭代器 合并创建元组对象 (header,column)
-        let row = headers
+use crate::cli::{ColumnType, CsvOpts, OutputFormat};
+
+use super::{
+    csv_filter::Filter,
+    csv_infer::{resolve_column_types, typed_row, TypeInferer},
+    csv_protect::ColumnProtector,
+    csv_reader::{check_width, open_csv, open_csv_flexible, record_error, row_error},
+    csv_template::TemplateWriter,
+    format::FormatOptions,
+    projection::Projection,
+    record_writer::RecordWriter,
+    sql_writer::SqlTable,
+    unflatten::{parse_path, unflatten_row},
+};
+
+/// 转换数据 逐行读取逐行写出
+pub fn process_csv(opts: &CsvOpts, output: &str) -> Result<()> {
+    if opts.bom && opts.format != OutputFormat::CSV {
+        return Err(anyhow::anyhow!("--bom only applies to csv output"));
+    }
+    // SQL需要在建表时确定列类型
+    let is_sql = opts.format == OutputFormat::SQL || opts.format == OutputFormat::SQLITE;
+    // 标准输入无法读取两遍 扫描全部行推断类型会把整个输入缓存在内存中
+    if (opts.infer || is_sql) && opts.input == "-" && opts.infer_rows == 0 {
+        return Err(anyhow::anyhow!(
+            "Type inference on stdin requires --infer-rows greater than 0"
+        ));
+    }
+    // 读取文件 宽松模式下由每一行自行检查列数
+    let open = if opts.lenient {
+        open_csv_flexible
+    } else {
+        open_csv
+    };
+    let (mut reader, headers) = open(&opts.input, &opts.reader)?;
+
+    // 推断类型时缓存的样本行
+    let mut sample = vec![];
+    let inferred = if opts.infer || is_sql {
+        // 出错的行不参与推断 在输出时报告或跳过
+        let mut inferer = TypeInferer::new(headers.len());
+        if opts.infer_rows > 0 {
+            // 只用前几行推断 缓存样本行
+            for row in reader.records() {
+                if let Ok(row) = &row {
+                    if row.len() == headers.len() {
+                        inferer.observe(row);
+                    }
+                }
+                sample.push(row);
+                if sample.len() == opts.infer_rows {
+                    break;
+                }
+            }
+        } else {
+            // 文件先完整扫描一遍 保证整列类型一致且不占用内存
+            let (mut scan, _) = open(&opts.input, &opts.reader)?;
+            for row in scan.records().flatten() {
+                if row.len() == headers.len() {
+                    inferer.observe(&row);
+                }
+            }
+        }
+        Some(inferer.finish())
+    } else {
+        None
+    };
+    // 过滤条件和 --types 都使用原始列名
+    let filter = opts
+        .filter
+        .as_deref()
+        .map(|expr| Filter::parse(expr, &headers))
+        .transpose()?;
+    // 确定每一列的类型
+    let mut types = resolve_column_types(&headers, inferred, &opts.types)?;
+    // 列保护 处理后的列都是字符串
+    let protector = ColumnProtector::new(&headers, &opts.protect)?;
+    for idx in protector.iter().flat_map(|p| p.indices()) {
+        types[idx] = ColumnType::String;
+    }
+    let raw_headers = headers.clone();
+    // 列投影 之后都使用输出列
+    let projection = Projection::new(&headers, &opts.select, &opts.exclude, &opts.rename)?;
+    let headers = projection.headers();
+    let types: Vec<_> = projection.indices().iter().map(|&i| types[i]).collect();
+    // 嵌套输出时 预先解析每一列的路径
+    let paths = opts.unflatten.then(|| {
+        headers
             .iter()
-            .zip(row?.iter())
-            .collect::<serde_json::Value>();
-        // 添加到结果集
-        record.push(row);
-    }
-    //  转换数据格式
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&record)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&record)?,
-        OutputFormat::Toml => toml::to_string_pretty(&HashMap::from([("data", record)]))?,
+            .map(|h| parse_path(h, &opts.key_separator))
+            .collect::<Vec<_>>()
+    });
+
+    // 嵌套输出时列与原始列不再对应 由写入时推断
+    let table = SqlTable {
+        name: opts.sql.table_name.clone().unwrap_or_else(|| {
+            std::path::Path::new(&opts.input)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .filter(|stem| *stem != "-")
+                .unwrap_or("data")
+                .to_string()
+        }),
+        dialect: opts.sql.dialect,
+        batch_size: opts.sql.batch_size,
+        columns: paths.is_none().then(|| {
+            headers
+                .iter()
+                .map(String::from)
+                .zip(types.iter().copied())
+                .collect()
+        }),
+    };
+
+    // 转换一行 被过滤时返回None
+    let convert = |row: &StringRecord| -> Result<Option<Value>> {
+        check_width(row, raw_headers.len())?;
+        if filter.as_ref().is_some_and(|f| !f.matches(row)) {
+            return Ok(None);
+        }
+        let protected;
+        let row = match &protector {
+            Some(protector) => {
+                protected = protector.apply(&raw_headers, row)?;
+                &protected
+            }
+            None => row,
+        };
+        let row = projection.apply(row);
+        // 按列类型 合并创建对象 {header: column}
+        let value = typed_row(headers, &row, &types)?;
+        let value = match &paths {
+            Some(paths) => {
+                let line = row.position().map(|p| p.line()).unwrap_or_default();
+                unflatten_row(paths, value, line)?
+            }
+            None => Value::Object(value),
+        };
+        Ok(Some(value))
     };
 
-    // 将结果写入文件
-    std::fs::write(output, content)?;
+    // 遍历结果 先输出样本行 再输出剩余的行
+    let mut writer: Box<dyn RecordWriter> = match &opts.template.template {
+        Some(template) => Box::new(TemplateWriter::new(
+            template,
+            opts.template.per_row.as_deref(),
+            headers,
+            output,
+        )?),
+        None => opts.format.writer(
+            output,
+            &FormatOptions {
+                table,
+                bom: opts.bom,
+                xml_root: opts.xml.xml_root.clone(),
+                xml_row: opts.xml.xml_row.clone(),
+            },
+        )?,
+    };
+    let mut rejects = Rejects::new(&opts.rejects, &raw_headers, opts.reader.delimiter);
+    for row in sample.into_iter().chain(reader.records()) {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) if opts.lenient => {
+                let e = row_error(e)?;
+                rejects.write(e.line, &e.reason, &StringRecord::new())?;
+                continue;
+            }
+            Err(e) => return Err(record_error(e)),
+        };
+        let line = row.position().map(|p| p.line()).unwrap_or_default();
+        match convert(&row) {
+            Ok(Some(value)) => writer.write(&value)?,
+            Ok(None) => {}
+            Err(e) if opts.lenient => rejects.write(line, &e.to_string(), &row)?,
+            Err(e) => return Err(e),
+        }
+    }
+    writer.finish()?;
+    rejects.finish()
+}
+
+/// 宽松模式下被跳过的行 第一次出现时才创建文件
+struct Rejects<'a> {
+    path: &'a str,
+    headers: &'a StringRecord,
+    delimiter: u8,
+    writer: Option<csv::Writer<File>>,
+    count: usize,
+}
+
+impl<'a> Rejects<'a> {
+    fn new(path: &'a str, headers: &'a StringRecord, delimiter: char) -> Self {
+        Self {
+            path,
+            headers,
+            delimiter: delimiter as u8,
+            writer: None,
+            count: 0,
+        }
+    }
+
+    // 写入原始的行 前面加上行号和原因，无法解析的行只有行号和原因
+    fn write(&mut self, line: u64, reason: &str, row: &StringRecord) -> Result<()> {
+        if self.writer.is_none() {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(self.delimiter)
+                .flexible(true)
+                .from_path(self.path)?;
+            writer.write_record(["line", "error"].into_iter().chain(self.headers))?;
+            self.writer = Some(writer);
+        }
+        let line = line.to_string();
+        self.writer
+            .as_mut()
+            .unwrap()
+            .write_record([line.as_str(), reason].into_iter().chain(row))?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Some(mut writer) = self.writer {
+            writer.flush()?;
+            tracing::warn!("跳过 {} 行 已写入 {}", self.count, self.path);
+        }
+        Ok(())
+    }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_infer_stdin_requires_rows() {
+        for args in [
+            vec!["csv", "-i", "-", "--infer"],
+            vec!["csv", "-i", "-", "--format", "sql"],
+        ] {
+            let opts = CsvOpts::parse_from(args);
+            let err = process_csv(&opts, "-").unwrap_err();
+            assert!(err.to_string().contains("--infer-rows"));
+        }
+    }
+
+    #[test]
+    fn test_lenient_writes_rejects() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("output.ndjson");
+        let rejects = dir.path().join("rejects.csv");
+        let args = [
+            "csv",
+            "-i",
+            "fixtures/csv_invalid.csv",
+            "--format",
+            "ndjson",
+            "--types",
+            "Kit Number:int",
+        ];
+
+        // 默认遇到第一处错误即停止 并报告行号
+        let opts = CsvOpts::parse_from(args);
+        let err = process_csv(&opts, output.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().starts_with("line 3, column Kit Number"));
+
+        let opts = CsvOpts::parse_from(args.into_iter().chain([
+            "--lenient",
+            "--rejects",
+            rejects.to_str().unwrap(),
+        ]));
+        process_csv(&opts, output.to_str().unwrap())?;
+        let converted = std::fs::read_to_string(&output)?;
+        let rejected = std::fs::read_to_string(&rejects)?;
+
+        assert_eq!(converted.lines().count(), 4);
+        let rejected: Vec<&str> = rejected.lines().collect();
+        assert_eq!(
+            rejected[0],
+            "line,error,Name,Position,Nationality,Kit Number"
+        );
+        assert!(rejected[1].starts_with("3,"));
+        assert!(rejected[1].ends_with(",Mattia Perin,Goalkeeper,Italy,abc"));
+        assert_eq!(
+            rejected[2],
+            "5,\"line 5: expected 4 fields, found 3\",Short,Goalkeeper,Italy"
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,525 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    io::{IsTerminal, Write},
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+use serde_json::{json, Map, Value};
+
+use crate::{cli::CsvDiffOpts, get_writer};
+
+use super::{
+    csv_reader::{column_index, open_csv, CsvReader},
+    csv_stats::format_table,
+    record_writer::record_writer,
+};
+
+/// 比较结果的统计
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DiffSummary {
+    pub added: u64,
+    pub removed: u64,
+    pub modified: u64,
+    /// 只在新文件中出现的列
+    pub added_columns: Vec<String>,
+    /// 只在旧文件中出现的列
+    pub removed_columns: Vec<String>,
+}
+
+impl DiffSummary {
+    /// 是否没有任何差异
+    pub fn is_empty(&self) -> bool {
+        self.added == 0
+            && self.removed == 0
+            && self.modified == 0
+            && self.added_columns.is_empty()
+            && self.removed_columns.is_empty()
+    }
+}
+
+/// 比较两个CSV并输出差异
+pub fn process_csv_diff(opts: &CsvDiffOpts) -> Result<DiffSummary> {
+    match opts.format {
+        Some(format) => {
+            let mut writer = record_writer(format, &opts.output)?;
+            let summary = diff_csv(opts, |entry| writer.write(&entry))?;
+            writer.finish()?;
+            Ok(summary)
+        }
+        None => {
+            // 表格需要知道每一列的宽度 只能先收集全部差异
+            let mut entries = vec![];
+            let summary = diff_csv(opts, |entry| {
+                entries.push(entry);
+                Ok(())
+            })?;
+            let color = !opts.no_color
+                && opts.output == "-"
+                && std::io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none();
+            let mut writer = get_writer(&opts.output)?;
+            write_diff_table(&mut writer, &entries, &summary, color)?;
+            writer.flush()?;
+            Ok(summary)
+        }
+    }
+}
+
+/// 按主键比较 每个差异都会交给emit处理
+/// 差异记录形如 {"change": "modified", "key": {...}, "changes": [{"column", "old", "new"}]}
+pub fn diff_csv(
+    opts: &CsvDiffOpts,
+    mut emit: impl FnMut(Value) -> Result<()>,
+) -> Result<DiffSummary> {
+    let (old_reader, old_headers) = open_csv(&opts.old, &opts.reader)?;
+    let (new_reader, new_headers) = open_csv(&opts.new, &opts.reader)?;
+    let layout = Layout::new(&opts.key, &old_headers, &new_headers)?;
+    let mut summary = DiffSummary {
+        added_columns: layout.added_columns.clone(),
+        removed_columns: layout.removed_columns.clone(),
+        ..Default::default()
+    };
+
+    let mut report = |change: Change| -> Result<()> {
+        let entry = match change {
+            Change::Added(row) => {
+                summary.added += 1;
+                json!({
+                    "change": "added",
+                    "key": layout.key(&row, Side::New),
+                    "row": record_map(&new_headers, &row),
+                })
+            }
+            Change::Removed(row) => {
+                summary.removed += 1;
+                json!({
+                    "change": "removed",
+                    "key": layout.key(&row, Side::Old),
+                    "row": record_map(&old_headers, &row),
+                })
+            }
+            Change::Both(old, new) => {
+                let changes = layout.changes(&old, &new);
+                if changes.is_empty() {
+                    return Ok(());
+                }
+                summary.modified += 1;
+                json!({
+                    "change": "modified",
+                    "key": layout.key(&new, Side::New),
+                    "changes": changes,
+                })
+            }
+        };
+        emit(entry)
+    };
+
+    if opts.sorted {
+        merge_sorted(&layout, old_reader, new_reader, &mut report)?;
+    } else {
+        match_by_key(&layout, old_reader, new_reader, &mut report)?;
+    }
+    Ok(summary)
+}
+
+/// 一对匹配的结果
+enum Change {
+    Added(StringRecord),
+    Removed(StringRecord),
+    Both(StringRecord, StringRecord),
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Old,
+    New,
+}
+
+/// 两个文件的列对应关系
+struct Layout {
+    key_names: Vec<String>,
+    old_key: Vec<usize>,
+    new_key: Vec<usize>,
+    /// 两边都有的列 (列名, 旧下标, 新下标)
+    common: Vec<(String, usize, usize)>,
+    added_columns: Vec<String>,
+    removed_columns: Vec<String>,
+}
+
+impl Layout {
+    fn new(key: &[String], old: &StringRecord, new: &StringRecord) -> Result<Self> {
+        let old_key = key
+            .iter()
+            .map(|k| column_index(old, k))
+            .collect::<Result<Vec<_>>>()?;
+        let new_key = key
+            .iter()
+            .map(|k| column_index(new, k))
+            .collect::<Result<Vec<_>>>()?;
+        let common = old
+            .iter()
+            .enumerate()
+            .filter_map(|(oi, name)| {
+                let ni = new.iter().position(|n| n == name)?;
+                Some((name.to_string(), oi, ni))
+            })
+            .collect();
+        Ok(Self {
+            key_names: old_key.iter().map(|&i| old[i].to_string()).collect(),
+            old_key,
+            new_key,
+            common,
+            added_columns: new
+                .iter()
+                .filter(|n| !old.iter().any(|o| o == *n))
+                .map(String::from)
+                .collect(),
+            removed_columns: old
+                .iter()
+                .filter(|o| !new.iter().any(|n| n == *o))
+                .map(String::from)
+                .collect(),
+        })
+    }
+
+    fn key_values(&self, row: &StringRecord, side: Side) -> Vec<String> {
+        let indices = match side {
+            Side::Old => &self.old_key,
+            Side::New => &self.new_key,
+        };
+        indices
+            .iter()
+            .map(|&i| row.get(i).unwrap_or_default().to_string())
+            .collect()
+    }
+
+    fn key(&self, row: &StringRecord, side: Side) -> Value {
+        let map: Map<String, Value> = self
+            .key_names
+            .iter()
+            .cloned()
+            .zip(self.key_values(row, side).into_iter().map(Value::from))
+            .collect();
+        Value::Object(map)
+    }
+
+    // 两边都有的列中 值不同的单元格
+    fn changes(&self, old: &StringRecord, new: &StringRecord) -> Vec<Value> {
+        self.common
+            .iter()
+            .filter_map(|(name, oi, ni)| {
+                let (o, n) = (
+                    old.get(*oi).unwrap_or_default(),
+                    new.get(*ni).unwrap_or_default(),
+                );
+                (o != n).then(|| json!({"column": name, "old": o, "new": n}))
+            })
+            .collect()
+    }
+}
+
+// 旧文件放入内存 新文件逐行匹配
+fn match_by_key(
+    layout: &Layout,
+    mut old_reader: CsvReader,
+    mut new_reader: CsvReader,
+    report: &mut impl FnMut(Change) -> Result<()>,
+) -> Result<()> {
+    let mut old_rows: Vec<Option<StringRecord>> = vec![];
+    let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+    for row in old_reader.records() {
+        let row = row?;
+        let key = layout.key_values(&row, Side::Old);
+        if index.insert(key.clone(), old_rows.len()).is_some() {
+            return Err(duplicate_key(&key, &row));
+        }
+        old_rows.push(Some(row));
+    }
+
+    let mut seen = HashSet::new();
+    for row in new_reader.records() {
+        let row = row?;
+        let key = layout.key_values(&row, Side::New);
+        if !seen.insert(key.clone()) {
+            return Err(duplicate_key(&key, &row));
+        }
+        match index.get(&key).and_then(|&i| old_rows[i].take()) {
+            Some(old) => report(Change::Both(old, row))?,
+            None => report(Change::Added(row))?,
+        }
+    }
+    // 剩下的都是被删除的行 按旧文件的顺序输出
+    for row in old_rows.into_iter().flatten() {
+        report(Change::Removed(row))?;
+    }
+    Ok(())
+}
+
+// 两个文件都按主键排序 同时向前推进
+fn merge_sorted(
+    layout: &Layout,
+    mut old_reader: CsvReader,
+    mut new_reader: CsvReader,
+    report: &mut impl FnMut(Change) -> Result<()>,
+) -> Result<()> {
+    let mut old_rows = SortedRows::new(old_reader.records(), layout, Side::Old);
+    let mut new_rows = SortedRows::new(new_reader.records(), layout, Side::New);
+    let mut old = old_rows.next()?;
+    let mut new = new_rows.next()?;
+    loop {
+        match (old.take(), new.take()) {
+            (None, None) => break,
+            (Some((_, o)), None) => {
+                report(Change::Removed(o))?;
+                old = old_rows.next()?;
+            }
+            (None, Some((_, n))) => {
+                report(Change::Added(n))?;
+                new = new_rows.next()?;
+            }
+            (Some((ok, o)), Some((nk, n))) => match compare_keys(&ok, &nk) {
+                Ordering::Less => {
+                    report(Change::Removed(o))?;
+                    old = old_rows.next()?;
+                    new = Some((nk, n));
+                }
+                Ordering::Greater => {
+                    report(Change::Added(n))?;
+                    old = Some((ok, o));
+                    new = new_rows.next()?;
+                }
+                Ordering::Equal => {
+                    report(Change::Both(o, n))?;
+                    old = old_rows.next()?;
+                    new = new_rows.next()?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/// 按主键顺序读取 并检查是否真的有序
+struct SortedRows<'a, I> {
+    rows: I,
+    layout: &'a Layout,
+    side: Side,
+    last: Option<Vec<String>>,
+}
+
+impl<'a, I: Iterator<Item = csv::Result<StringRecord>>> SortedRows<'a, I> {
+    fn new(rows: I, layout: &'a Layout, side: Side) -> Self {
+        Self {
+            rows,
+            layout,
+            side,
+            last: None,
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<(Vec<String>, StringRecord)>> {
+        let Some(row) = self.rows.next() else {
+            return Ok(None);
+        };
+        let row = row?;
+        let key = self.layout.key_values(&row, self.side);
+        if let Some(last) = &self.last {
+            match compare_keys(last, &key) {
+                Ordering::Less => {}
+                Ordering::Equal => return Err(duplicate_key(&key, &row)),
+                Ordering::Greater => {
+                    let line = row.position().map(|p| p.line()).unwrap_or_default();
+                    return Err(anyhow::anyhow!(
+                        "line {}: key {} is not sorted, remove --sorted or sort the file first",
+                        line,
+                        key.join(",")
+                    ));
+                }
+            }
+        }
+        self.last = Some(key.clone());
+        Ok(Some((key, row)))
+    }
+}
+
+/// 逐列比较主键 都是数字时按数值比较
+pub fn compare_keys(a: &[String], b: &[String]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| match (a.parse::<f64>(), b.parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            _ => a.cmp(b),
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn duplicate_key(key: &[String], row: &StringRecord) -> anyhow::Error {
+    let line = row.position().map(|p| p.line()).unwrap_or_default();
+    anyhow::anyhow!("line {}: duplicate key {}", line, key.join(","))
+}
+
+fn record_map(headers: &StringRecord, row: &StringRecord) -> Value {
+    let map: Map<String, Value> = headers
+        .iter()
+        .zip(row.iter())
+        .map(|(h, v)| (h.to_string(), Value::from(v)))
+        .collect();
+    Value::Object(map)
+}
+
+// 输出差异表格 新增为绿色，删除为红色，修改为黄色
+fn write_diff_table(
+    writer: &mut impl Write,
+    entries: &[Value],
+    summary: &DiffSummary,
+    color: bool,
+) -> Result<()> {
+    let text = |v: &Value| v.as_str().unwrap_or_default().to_string();
+    let key = |entry: &Value| {
+        entry["key"]
+            .as_object()
+            .map(|k| k.values().map(text).collect::<Vec<_>>().join(","))
+            .unwrap_or_default()
+    };
+    let mut rows = vec![];
+    let mut colors = vec![];
+    for entry in entries {
+        let (mark, code) = match entry["change"].as_str() {
+            Some("added") => ("+", "32"),
+            Some("removed") => ("-", "31"),
+            _ => ("~", "33"),
+        };
+        match entry["changes"].as_array() {
+            Some(changes) => {
+                for change in changes {
+                    rows.push(vec![
+                        mark.to_string(),
+                        key(entry),
+                        text(&change["column"]),
+                        text(&change["old"]),
+                        text(&change["new"]),
+                    ]);
+                    colors.push(code);
+                }
+            }
+            None => {
+                let row = entry["row"]
+                    .as_object()
+                    .map(|r| r.values().map(text).collect::<Vec<_>>().join(","))
+                    .unwrap_or_default();
+                let (old, new) = if mark == "+" {
+                    (String::new(), row)
+                } else {
+                    (row, String::new())
+                };
+                rows.push(vec![mark.to_string(), key(entry), String::new(), old, new]);
+                colors.push(code);
+            }
+        }
+    }
+
+    let lines = format_table(&["", "key", "column", "old", "new"], &rows);
+    for (i, line) in lines.iter().enumerate() {
+        // 前两行是表头和分隔线
+        match i.checked_sub(2).map(|i| colors[i]) {
+            Some(code) if color => writeln!(writer, "\x1b[{}m{}\x1b[0m", code, line)?,
+            _ => writeln!(writer, "{}", line)?,
+        }
+    }
+    writeln!(
+        writer,
+        "\n{} added, {} removed, {} modified",
+        summary.added, summary.removed, summary.modified
+    )?;
+    if !summary.added_columns.is_empty() {
+        writeln!(
+            writer,
+            "added columns: {}",
+            summary.added_columns.join(", ")
+        )?;
+    }
+    if !summary.removed_columns.is_empty() {
+        writeln!(
+            writer,
+            "removed columns: {}",
+            summary.removed_columns.join(", ")
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn diff(args: &[&str]) -> Result<(DiffSummary, Vec<Value>)> {
+        let opts = CsvDiffOpts::parse_from(
+            [
+                "diff",
+                "fixtures/csv_diff_old.csv",
+                "fixtures/csv_diff_new.csv",
+            ]
+            .iter()
+            .chain(args),
+        );
+        let mut entries = vec![];
+        let summary = diff_csv(&opts, |e| {
+            entries.push(e);
+            Ok(())
+        })?;
+        Ok((summary, entries))
+    }
+
+    #[test]
+    fn test_diff_by_key() -> Result<()> {
+        let (summary, entries) = diff(&["--key", "ID"])?;
+        assert_eq!(
+            (summary.added, summary.removed, summary.modified),
+            (1, 1, 2)
+        );
+        assert!(!summary.is_empty());
+
+        let buffon = &entries[0];
+        assert_eq!(buffon["change"], "modified");
+        assert_eq!(buffon["key"], json!({"ID": "3"}));
+        assert_eq!(
+            buffon["changes"],
+            json!([{"column": "Kit Number", "old": "77", "new": "1"}])
+        );
+        assert_eq!(entries[1]["change"], "added");
+        assert_eq!(entries[3]["change"], "removed");
+        assert_eq!(entries[3]["row"]["Name"], "Mattia Perin");
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_sorted_matches_unsorted() -> Result<()> {
+        let (unsorted, mut a) = diff(&["--key", "ID"])?;
+        let (sorted, mut b) = diff(&["--key", "ID", "--sorted"])?;
+        assert_eq!(unsorted, sorted);
+        let key = |v: &Value| v["key"]["ID"].as_str().unwrap().parse::<u32>().unwrap();
+        a.sort_by_key(key);
+        b.sort_by_key(key);
+        assert_eq!(a, b);
+
+        // 按名字并没有排序
+        assert!(diff(&["--key", "Name", "--sorted"]).is_err());
+        assert!(diff(&["--key", "Position"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_table() -> Result<()> {
+        let (summary, entries) = diff(&["--key", "ID"])?;
+        let mut buf = Vec::new();
+        write_diff_table(&mut buf, &entries, &summary, false)?;
+        let table = String::from_utf8(buf)?;
+        assert!(table.contains("~  3    Kit Number  77"));
+        assert!(table.ends_with("1 added, 1 removed, 2 modified\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,489 @@
+use std::cmp::Ordering;
+
+use anyhow::Result;
+use csv::StringRecord;
+use regex::Regex;
+
+/// 行过滤表达式
+/// 支持 == != < <= > >=、and or not、contains、=~ (matches)、in [...]、is [not] null
+/// 列名可以直接书写，包含空格等字符时使用反引号，如 `Kit Number` > 10
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// 解析表达式 并将列名绑定到列下标，未知列直接报错
+    pub fn parse(source: &str, headers: &StringRecord) -> Result<Self> {
+        let tokens = tokenize(source).map_err(|e| e.report(source))?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            headers,
+            end: source.chars().count(),
+        };
+        let expr = parser.parse().map_err(|e| e.report(source))?;
+        Ok(Self { expr })
+    }
+
+    /// 判断一行数据是否满足条件
+    pub fn matches(&self, record: &StringRecord) -> bool {
+        self.expr.eval(record)
+    }
+}
+
+/// 表达式节点
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, CompareOp, Operand),
+    Contains(Operand, Operand),
+    Matches(Operand, Regex),
+    In(Operand, Vec<String>),
+    IsNull(Operand),
+}
+
+/// 操作数 列或者字面量
+#[derive(Debug, Clone)]
+enum Operand {
+    Column(usize),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Expr {
+    fn eval(&self, record: &StringRecord) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(record) && b.eval(record),
+            Expr::Or(a, b) => a.eval(record) || b.eval(record),
+            Expr::Not(a) => !a.eval(record),
+            Expr::Compare(a, op, b) => {
+                let (a, b) = (a.value(record), b.value(record));
+                match op {
+                    CompareOp::Eq => compare(a, b) == Some(Ordering::Equal),
+                    CompareOp::Ne => compare(a, b) != Some(Ordering::Equal),
+                    // 空值不参与大小比较
+                    _ if a.is_empty() || b.is_empty() => false,
+                    CompareOp::Lt => compare(a, b) == Some(Ordering::Less),
+                    CompareOp::Le => {
+                        matches!(compare(a, b), Some(Ordering::Less | Ordering::Equal))
+                    }
+                    CompareOp::Gt => compare(a, b) == Some(Ordering::Greater),
+                    CompareOp::Ge => {
+                        matches!(compare(a, b), Some(Ordering::Greater | Ordering::Equal))
+                    }
+                }
+            }
+            Expr::Contains(a, b) => a.value(record).contains(b.value(record)),
+            Expr::Matches(a, re) => re.is_match(a.value(record)),
+            Expr::In(a, list) => {
+                let a = a.value(record);
+                list.iter().any(|b| compare(a, b) == Some(Ordering::Equal))
+            }
+            Expr::IsNull(a) => a.value(record).is_empty(),
+        }
+    }
+}
+
+impl Operand {
+    fn value<'a>(&'a self, record: &'a StringRecord) -> &'a str {
+        match self {
+            Operand::Column(idx) => record.get(*idx).unwrap_or_default(),
+            Operand::Literal(s) => s,
+        }
+    }
+}
+
+// 两边都是数字时按数字比较 否则按字符串比较
+fn compare(a: &str, b: &str) -> Option<Ordering> {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b),
+        _ => Some(a.cmp(b)),
+    }
+}
+
+type ParseResult<T> = std::result::Result<T, ParseError>;
+
+/// 带位置的解析错误 位置为字符下标
+#[derive(Debug)]
+struct ParseError {
+    pos: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(pos: usize, message: impl Into<String>) -> Self {
+        Self {
+            pos,
+            message: message.into(),
+        }
+    }
+
+    // 输出带箭头的错误信息
+    fn report(self, source: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Invalid --where expression at column {}: {}\n  {}\n  {}^",
+            self.pos + 1,
+            self.message,
+            source,
+            " ".repeat(self.pos)
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Column(String),
+    Str(String),
+    Number(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+// 词法分析 返回Token及其起始位置
+fn tokenize(source: &str) -> ParseResult<Vec<(Token, usize)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ',' => Token::Comma,
+            '"' | '\'' | '`' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(ParseError::new(start, "unterminated quote")),
+                        Some('\\') if c != '`' && i + 1 < chars.len() => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&q) if q == c => break,
+                        Some(&ch) => {
+                            value.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                if c == '`' {
+                    Token::Column(value)
+                } else {
+                    Token::Str(value)
+                }
+            }
+            '=' | '!' | '<' | '>' => {
+                let next = chars.get(i + 1).copied();
+                let (op, len) = match (c, next) {
+                    ('=', Some('=')) => ("==", 2),
+                    ('=', Some('~')) => ("=~", 2),
+                    ('!', Some('=')) => ("!=", 2),
+                    ('<', Some('=')) => ("<=", 2),
+                    ('>', Some('=')) => (">=", 2),
+                    ('<', _) => ("<", 1),
+                    ('>', _) => (">", 1),
+                    ('=', _) => ("==", 1),
+                    _ => return Err(ParseError::new(start, "unexpected character '!'")),
+                };
+                tokens.push((Token::Op(op), start));
+                i += len;
+                continue;
+            }
+            c if c.is_ascii_digit()
+                || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                let mut value = String::from(c);
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    value.push(chars[i]);
+                    i += 1;
+                }
+                tokens.push((Token::Number(value), start));
+                continue;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut value = String::new();
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.'))
+                {
+                    value.push(chars[i]);
+                    i += 1;
+                }
+                tokens.push((Token::Ident(value), start));
+                continue;
+            }
+            c => {
+                return Err(ParseError::new(
+                    start,
+                    format!("unexpected character '{}'", c),
+                ))
+            }
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// 递归下降解析器
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    headers: &'a StringRecord,
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn parse(&mut self) -> ParseResult<Expr> {
+        let expr = self.parse_or()?;
+        match self.tokens.get(self.pos) {
+            None => Ok(expr),
+            Some((_, at)) => Err(ParseError::new(
+                *at,
+                "expected 'and', 'or' or end of expression",
+            )),
+        }
+    }
+
+    fn parse_or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_not()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> ParseResult<Expr> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_predicate()
+    }
+
+    fn parse_predicate(&mut self) -> ParseResult<Expr> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.parse_or()?;
+            self.expect(Token::RParen, "expected ')'")?;
+            return Ok(expr);
+        }
+
+        let left = self.parse_operand()?;
+        let at = self.at();
+        match self.next() {
+            Some(Token::Op("=~")) => self.parse_regex(left),
+            Some(Token::Op(op)) => {
+                let op = match op {
+                    "==" => CompareOp::Eq,
+                    "!=" => CompareOp::Ne,
+                    "<" => CompareOp::Lt,
+                    "<=" => CompareOp::Le,
+                    ">" => CompareOp::Gt,
+                    _ => CompareOp::Ge,
+                };
+                Ok(Expr::Compare(left, op, self.parse_operand()?))
+            }
+            Some(Token::Ident(kw)) => match kw.to_lowercase().as_str() {
+                "contains" => Ok(Expr::Contains(left, self.parse_operand()?)),
+                "matches" => self.parse_regex(left),
+                "in" => Ok(Expr::In(left, self.parse_list()?)),
+                "not" if self.eat_keyword("in") => {
+                    Ok(Expr::Not(Box::new(Expr::In(left, self.parse_list()?))))
+                }
+                "is" => {
+                    let negate = self.eat_keyword("not");
+                    if !self.eat_keyword("null") {
+                        return Err(ParseError::new(self.at(), "expected 'null'"));
+                    }
+                    let expr = Expr::IsNull(left);
+                    Ok(if negate {
+                        Expr::Not(Box::new(expr))
+                    } else {
+                        expr
+                    })
+                }
+                _ => Err(ParseError::new(at, "expected an operator")),
+            },
+            _ => Err(ParseError::new(at, "expected an operator")),
+        }
+    }
+
+    fn parse_regex(&mut self, left: Operand) -> ParseResult<Expr> {
+        let at = self.at();
+        match self.next() {
+            Some(Token::Str(pattern)) => Regex::new(&pattern)
+                .map(|re| Expr::Matches(left, re))
+                .map_err(|e| ParseError::new(at, format!("invalid regex, {}", e))),
+            _ => Err(ParseError::new(at, "expected a quoted regex")),
+        }
+    }
+
+    fn parse_list(&mut self) -> ParseResult<Vec<String>> {
+        self.expect(Token::LBracket, "expected '['")?;
+        let mut list = vec![];
+        if self.peek() == Some(&Token::RBracket) {
+            self.pos += 1;
+            return Ok(list);
+        }
+        loop {
+            let at = self.at();
+            match self.parse_operand()? {
+                Operand::Literal(s) => list.push(s),
+                Operand::Column(_) => return Err(ParseError::new(at, "expected a literal")),
+            }
+            let at = self.at();
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RBracket) => break,
+                _ => return Err(ParseError::new(at, "expected ',' or ']'")),
+            }
+        }
+        Ok(list)
+    }
+
+    fn parse_operand(&mut self) -> ParseResult<Operand> {
+        let at = self.at();
+        match self.next() {
+            Some(Token::Str(s) | Token::Number(s)) => Ok(Operand::Literal(s)),
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" | "false" => Ok(Operand::Literal(name)),
+                "null" => Ok(Operand::Literal(String::new())),
+                _ => self.column(name, at),
+            },
+            Some(Token::Column(name)) => self.column(name, at),
+            _ => Err(ParseError::new(at, "expected a column or a value")),
+        }
+    }
+
+    // 绑定列名
+    fn column(&self, name: String, at: usize) -> ParseResult<Operand> {
+        self.headers
+            .iter()
+            .position(|h| h == name)
+            .map(Operand::Column)
+            .ok_or_else(|| ParseError::new(at, format!("unknown column '{}'", name)))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(s)) if s.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: Token, message: &str) -> ParseResult<()> {
+        let at = self.at();
+        if self.next() == Some(token) {
+            Ok(())
+        } else {
+            Err(ParseError::new(at, message))
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    // 当前Token的位置 没有Token时指向表达式结尾
+    fn at(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(_, at)| *at)
+            .unwrap_or(self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["Name", "Position", "Nationality", "Kit Number"])
+    }
+
+    fn matches(expr: &str, row: &[&str]) -> bool {
+        Filter::parse(expr, &headers())
+            .unwrap()
+            .matches(&StringRecord::from(row.to_vec()))
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let buffon = ["Gianluigi Buffon", "Goalkeeper", "Italy", "77"];
+        let ronaldo = ["Cristiano Ronaldo", "Forward", "Portugal", "7"];
+        let expr = r#"Nationality == "Italy" and `Kit Number` > 25"#;
+        assert!(matches(expr, &buffon));
+        assert!(!matches(expr, &ronaldo));
+        // 按数字比较而不是字符串
+        assert!(matches("`Kit Number` < 10", &ronaldo));
+        assert!(matches(
+            r#"not (Position in ["Goalkeeper", "Defender"]) or Name contains "Buffon""#,
+            &buffon
+        ));
+        assert!(matches(
+            r#"Name =~ "^Cris" and Position not in ["Goalkeeper"]"#,
+            &ronaldo
+        ));
+        assert!(matches("Name is not null", &ronaldo));
+        assert!(matches("Name is null", &["", "", "", ""]));
+        assert!(!matches("`Kit Number` > 5", &["", "", "", ""]));
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        let err = Filter::parse(r#"Nationality == "Italy" and Age > 25"#, &headers()).unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("column 28: unknown column 'Age'"));
+        assert!(err.ends_with(&format!("\n  {}^", " ".repeat(27))));
+
+        let err = Filter::parse("Nationality == ", &headers()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("column 16: expected a column or a value"));
+        assert!(Filter::parse(r#"Name =~ "(""#, &headers()).is_err());
+        assert!(Filter::parse("(Name == 'a'", &headers()).is_err());
+        assert!(Filter::parse("Name 'a'", &headers()).is_err());
+    }
+}
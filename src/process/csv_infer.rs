@@ -0,0 +1,297 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use csv::StringRecord;
+use serde_json::{Map, Value};
+
+use crate::cli::ColumnType;
+
+use super::csv_reader::column_index;
+
+/// 日期时间支持的格式 RFC3339之外的ISO-8601形式
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+/// 时间支持的格式
+const TIME_FORMATS: &[&str] = &["%H:%M:%S%.f", "%H:%M"];
+
+/// 列类型推断器 按行累积每一列的类型
+#[derive(Debug, Clone)]
+pub struct TypeInferer {
+    types: Vec<ColumnType>,
+}
+
+impl TypeInferer {
+    /// 所有列初始都为Null
+    pub fn new(width: usize) -> Self {
+        Self {
+            types: vec![ColumnType::Null; width],
+        }
+    }
+
+    /// 观察一行数据
+    pub fn observe(&mut self, record: &StringRecord) {
+        for (ty, cell) in self.types.iter_mut().zip(record.iter()) {
+            // 已经是字符串的列不需要再判断
+            if *ty != ColumnType::String {
+                *ty = merge_type(*ty, detect_type(cell));
+            }
+        }
+    }
+
+    /// 得到推断结果
+    pub fn finish(self) -> Vec<ColumnType> {
+        self.types
+    }
+}
+
+/// 确定每一列的类型
+/// infer为false时 除了overrides指定的列外都是字符串
+pub fn resolve_column_types(
+    headers: &StringRecord,
+    inferred: Option<Vec<ColumnType>>,
+    overrides: &[(String, ColumnType)],
+) -> Result<Vec<ColumnType>> {
+    let mut types = inferred.unwrap_or_else(|| vec![ColumnType::String; headers.len()]);
+    for (column, ty) in overrides {
+        types[column_index(headers, column)?] = *ty;
+    }
+    Ok(types)
+}
+
+/// 将一行数据按列类型转换为Json对象
+pub fn typed_row(
+    headers: &StringRecord,
+    record: &StringRecord,
+    types: &[ColumnType],
+) -> Result<Map<String, Value>> {
+    let mut row = Map::with_capacity(headers.len());
+    for ((header, cell), ty) in headers.iter().zip(record.iter()).zip(types) {
+        let value = typed_value(cell, *ty).map_err(|e| {
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            anyhow::anyhow!("line {}, column {}: {}", line, header, e)
+        })?;
+        row.insert(header.to_string(), value);
+    }
+    Ok(row)
+}
+
+/// 将单元格按类型转换
+pub fn typed_value(cell: &str, ty: ColumnType) -> Result<Value> {
+    if ty == ColumnType::String {
+        return Ok(Value::String(cell.to_string()));
+    }
+    if is_null(cell) {
+        return Ok(Value::Null);
+    }
+
+    let value = match ty {
+        ColumnType::Null => return Err(anyhow::anyhow!("expected null, got {:?}", cell)),
+        ColumnType::Bool => Value::Bool(
+            parse_bool(cell).ok_or_else(|| anyhow::anyhow!("expected bool, got {:?}", cell))?,
+        ),
+        // 显式指定类型时放宽限制 允许前导0
+        ColumnType::Int => Value::from(
+            cell.parse::<i64>()
+                .map_err(|_| anyhow::anyhow!("expected int, got {:?}", cell))?,
+        ),
+        ColumnType::Float => cell
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| anyhow::anyhow!("expected float, got {:?}", cell))?,
+        ColumnType::Date => Value::String(
+            parse_date(cell)
+                .ok_or_else(|| anyhow::anyhow!("expected date, got {:?}", cell))?
+                .to_string(),
+        ),
+        ColumnType::Time => Value::String(
+            parse_time(cell)
+                .ok_or_else(|| anyhow::anyhow!("expected time, got {:?}", cell))?
+                .to_string(),
+        ),
+        // 日期时间保留原文 避免丢失时区信息；日期与日期时间混合的列也接受只有日期的单元格
+        ColumnType::DateTime => {
+            if !is_datetime(cell) && parse_date(cell).is_none() {
+                return Err(anyhow::anyhow!("expected datetime, got {:?}", cell));
+            }
+            Value::String(cell.to_string())
+        }
+        ColumnType::String => unreachable!(),
+    };
+    Ok(value)
+}
+
+/// 识别单元格的类型
+pub fn detect_type(cell: &str) -> ColumnType {
+    if is_null(cell) {
+        ColumnType::Null
+    } else if parse_bool(cell).is_some() {
+        ColumnType::Bool
+    } else if parse_int(cell).is_some() {
+        ColumnType::Int
+    } else if parse_float(cell).is_some() {
+        ColumnType::Float
+    } else if parse_date(cell).is_some() {
+        ColumnType::Date
+    } else if is_datetime(cell) {
+        ColumnType::DateTime
+    } else if parse_time(cell).is_some() {
+        ColumnType::Time
+    } else {
+        ColumnType::String
+    }
+}
+
+/// 合并两个类型 得到能同时容纳二者的类型
+pub fn merge_type(a: ColumnType, b: ColumnType) -> ColumnType {
+    use ColumnType::*;
+    match (a, b) {
+        (a, b) if a == b => a,
+        (Null, t) | (t, Null) => t,
+        (Int, Float) | (Float, Int) => Float,
+        (Date, DateTime) | (DateTime, Date) => DateTime,
+        _ => String,
+    }
+}
+
+/// 空单元格和null都视为空值
+pub fn is_null(cell: &str) -> bool {
+    cell.is_empty() || cell == "null" || cell == "NULL"
+}
+
+fn parse_bool(cell: &str) -> Option<bool> {
+    match cell.to_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// 前导0的数字如邮编 视为字符串
+fn parse_int(cell: &str) -> Option<i64> {
+    let digits = cell.strip_prefix(['-', '+']).unwrap_or(cell);
+    if digits.is_empty()
+        || !digits.bytes().all(|b| b.is_ascii_digit())
+        || (digits.len() > 1 && digits.starts_with('0'))
+    {
+        return None;
+    }
+    cell.parse().ok()
+}
+
+/// 只接受十进制表示 排除 NaN inf 以及溢出为inf的 1e400 等
+fn parse_float(cell: &str) -> Option<f64> {
+    let digits = cell.strip_prefix(['-', '+']).unwrap_or(cell);
+    let valid = digits.bytes().any(|b| b.is_ascii_digit())
+        && digits
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'-' | b'+'))
+        && !(digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0."));
+    if valid {
+        cell.parse().ok().filter(|f: &f64| f.is_finite())
+    } else {
+        None
+    }
+}
+
+pub fn parse_date(cell: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(cell, "%Y-%m-%d").ok()
+}
+
+fn parse_time(cell: &str) -> Option<NaiveTime> {
+    TIME_FORMATS
+        .iter()
+        .find_map(|f| NaiveTime::parse_from_str(cell, f).ok())
+}
+
+fn is_datetime(cell: &str) -> bool {
+    DateTime::parse_from_rfc3339(cell).is_ok() || parse_naive_datetime(cell).is_some()
+}
+
+/// 不带时区的日期时间
+pub fn parse_naive_datetime(cell: &str) -> Option<NaiveDateTime> {
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(cell, f).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_type() {
+        assert_eq!(detect_type(""), ColumnType::Null);
+        assert_eq!(detect_type("TRUE"), ColumnType::Bool);
+        assert_eq!(detect_type("-42"), ColumnType::Int);
+        assert_eq!(detect_type("00123"), ColumnType::String);
+        assert_eq!(detect_type("3.14"), ColumnType::Float);
+        assert_eq!(detect_type("1e3"), ColumnType::Float);
+        assert_eq!(detect_type("NaN"), ColumnType::String);
+        assert_eq!(detect_type("1e400"), ColumnType::String);
+        assert_eq!(detect_type("2024-02-29"), ColumnType::Date);
+        assert_eq!(detect_type("2024-02-30"), ColumnType::String);
+        assert_eq!(
+            detect_type("2024-02-29T10:00:00+08:00"),
+            ColumnType::DateTime
+        );
+        assert_eq!(detect_type("23:59:01"), ColumnType::Time);
+    }
+
+    #[test]
+    fn test_infer_column_consistently() -> Result<()> {
+        let headers = StringRecord::from(vec!["id", "score", "zip", "note"]);
+        let rows = [
+            StringRecord::from(vec!["1", "9", "01234", ""]),
+            StringRecord::from(vec!["2", "8.5", "20100", ""]),
+            StringRecord::from(vec!["3", "", "10121", ""]),
+        ];
+        let mut inferer = TypeInferer::new(headers.len());
+        rows.iter().for_each(|r| inferer.observe(r));
+        let types = resolve_column_types(&headers, Some(inferer.finish()), &[])?;
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Int,
+                ColumnType::Float,
+                ColumnType::String,
+                ColumnType::Null
+            ]
+        );
+
+        // 整列统一按Float输出
+        let row = typed_row(&headers, &rows[0], &types)?;
+        assert_eq!(row["score"], serde_json::json!(9.0));
+        assert_eq!(row["zip"], serde_json::json!("01234"));
+        assert_eq!(typed_row(&headers, &rows[2], &types)?["score"], Value::Null);
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_overrides() -> Result<()> {
+        let headers = StringRecord::from(vec!["id", "zip"]);
+        let types = resolve_column_types(&headers, None, &[("zip".to_string(), ColumnType::Int)])?;
+        assert_eq!(types, vec![ColumnType::String, ColumnType::Int]);
+        assert!(
+            resolve_column_types(&headers, None, &[("age".to_string(), ColumnType::Int)]).is_err()
+        );
+        assert!(typed_value("abc", ColumnType::Int).is_err());
+
+        // 日期和日期时间混合的列合并为日期时间 只有日期的单元格保留原文
+        let ty = merge_type(
+            detect_type("2024-01-01"),
+            detect_type("2024-01-02T10:00:00"),
+        );
+        assert_eq!(ty, ColumnType::DateTime);
+        assert_eq!(
+            typed_value("2024-01-01", ty)?,
+            serde_json::json!("2024-01-01")
+        );
+        assert!(typed_value("01/02/2024", ty).is_err());
+        Ok(())
+    }
+}
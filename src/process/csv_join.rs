@@ -0,0 +1,301 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use anyhow::Result;
+use csv::StringRecord;
+
+use crate::cli::{CsvJoinOpts, JoinKind};
+
+use super::{
+    csv_diff::compare_keys,
+    csv_reader::{column_index, open_csv},
+    row_writer::RowWriter,
+};
+
+/// 连接两个CSV
+pub fn process_csv_join(opts: &CsvJoinOpts) -> Result<()> {
+    let (mut left, left_headers) = open_csv(&opts.left, &opts.reader)?;
+    let (mut right, right_headers) = open_csv(&opts.right, &opts.reader)?;
+    let right_key = if opts.right_key.is_empty() {
+        &opts.key
+    } else {
+        &opts.right_key
+    };
+    if right_key.len() != opts.key.len() {
+        return Err(anyhow::anyhow!(
+            "--key has {} columns but --right-key has {}",
+            opts.key.len(),
+            right_key.len()
+        ));
+    }
+    let layout = JoinLayout::new(&left_headers, &right_headers, &opts.key, right_key)?;
+
+    let mut writer = RowWriter::new(
+        &opts.output,
+        opts.format,
+        &layout.headers,
+        opts.reader.delimiter,
+    )?;
+    let mut emit = |left: Option<&StringRecord>, right: Option<&StringRecord>| {
+        writer.write(&layout.join(left, right))
+    };
+
+    if opts.sorted {
+        let mut left = KeyedGroups::new(left.records(), &layout.left_key);
+        let mut right = KeyedGroups::new(right.records(), &layout.right_key);
+        let mut l = left.next()?;
+        let mut r = right.next()?;
+        loop {
+            let order = match (&l, &r) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((lk, _)), Some((rk, _))) => compare_keys(lk, rk),
+            };
+            match order {
+                Ordering::Less => {
+                    if opts.kind != JoinKind::Inner {
+                        for row in &l.as_ref().unwrap().1 {
+                            emit(Some(row), None)?;
+                        }
+                    }
+                    l = left.next()?;
+                }
+                Ordering::Greater => {
+                    if opts.kind == JoinKind::Outer {
+                        for row in &r.as_ref().unwrap().1 {
+                            emit(None, Some(row))?;
+                        }
+                    }
+                    r = right.next()?;
+                }
+                Ordering::Equal => {
+                    for lrow in &l.as_ref().unwrap().1 {
+                        for rrow in &r.as_ref().unwrap().1 {
+                            emit(Some(lrow), Some(rrow))?;
+                        }
+                    }
+                    l = left.next()?;
+                    r = right.next()?;
+                }
+            }
+        }
+    } else {
+        // 右表按主键分组放入内存 左表逐行匹配
+        let mut groups: Vec<(Vec<StringRecord>, bool)> = vec![];
+        let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+        for row in right.records() {
+            let row = row?;
+            let key = key_values(&row, &layout.right_key);
+            let idx = *index.entry(key).or_insert_with(|| {
+                groups.push((vec![], false));
+                groups.len() - 1
+            });
+            groups[idx].0.push(row);
+        }
+        for row in left.records() {
+            let row = row?;
+            match index.get(&key_values(&row, &layout.left_key)) {
+                Some(&idx) => {
+                    groups[idx].1 = true;
+                    for rrow in &groups[idx].0 {
+                        emit(Some(&row), Some(rrow))?;
+                    }
+                }
+                None if opts.kind != JoinKind::Inner => emit(Some(&row), None)?,
+                None => {}
+            }
+        }
+        if opts.kind == JoinKind::Outer {
+            for (rows, _) in groups.iter().filter(|(_, matched)| !matched) {
+                for row in rows {
+                    emit(None, Some(row))?;
+                }
+            }
+        }
+    }
+    writer.finish()
+}
+
+/// 输出的列 左表的全部列加上右表除主键外的列
+struct JoinLayout {
+    headers: StringRecord,
+    left_width: usize,
+    left_key: Vec<usize>,
+    right_key: Vec<usize>,
+    /// 右表中输出的列
+    right_columns: Vec<usize>,
+}
+
+impl JoinLayout {
+    fn new(
+        left: &StringRecord,
+        right: &StringRecord,
+        left_key: &[String],
+        right_key: &[String],
+    ) -> Result<Self> {
+        let left_key = left_key
+            .iter()
+            .map(|k| column_index(left, k))
+            .collect::<Result<Vec<_>>>()?;
+        let right_key = right_key
+            .iter()
+            .map(|k| column_index(right, k))
+            .collect::<Result<Vec<_>>>()?;
+        let right_columns: Vec<usize> = (0..right.len())
+            .filter(|i| !right_key.contains(i))
+            .collect();
+
+        let mut headers: Vec<String> = left.iter().map(String::from).collect();
+        for &i in &right_columns {
+            // 与左表重名的列加上后缀
+            let mut name = right[i].to_string();
+            while headers.contains(&name) {
+                name.push_str("_right");
+            }
+            headers.push(name);
+        }
+        Ok(Self {
+            headers: StringRecord::from(headers),
+            left_width: left.len(),
+            left_key,
+            right_key,
+            right_columns,
+        })
+    }
+
+    // 合并一行 缺少的一边为空，只有右表时主键取右表的值
+    fn join(&self, left: Option<&StringRecord>, right: Option<&StringRecord>) -> StringRecord {
+        let mut row: Vec<&str> = match left {
+            Some(left) => (0..self.left_width)
+                .map(|i| left.get(i).unwrap_or_default())
+                .collect(),
+            None => vec![""; self.left_width],
+        };
+        if let (None, Some(right)) = (left, right) {
+            for (&l, &r) in self.left_key.iter().zip(&self.right_key) {
+                row[l] = right.get(r).unwrap_or_default();
+            }
+        }
+        row.extend(
+            self.right_columns
+                .iter()
+                .map(|&i| right.and_then(|r| r.get(i)).unwrap_or_default()),
+        );
+        StringRecord::from(row)
+    }
+}
+
+/// 按主键分组读取已排序的行 并检查顺序
+struct KeyedGroups<'a, I: Iterator> {
+    rows: std::iter::Peekable<I>,
+    key: &'a [usize],
+    last: Option<Vec<String>>,
+}
+
+impl<'a, I: Iterator<Item = csv::Result<StringRecord>>> KeyedGroups<'a, I> {
+    fn new(rows: I, key: &'a [usize]) -> Self {
+        Self {
+            rows: rows.peekable(),
+            key,
+            last: None,
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<(Vec<String>, Vec<StringRecord>)>> {
+        let Some(first) = self.rows.next() else {
+            return Ok(None);
+        };
+        let first = first?;
+        let key = key_values(&first, self.key);
+        if let Some(last) = &self.last {
+            if compare_keys(last, &key).is_gt() {
+                let line = first.position().map(|p| p.line()).unwrap_or_default();
+                return Err(anyhow::anyhow!(
+                    "line {}: key {} is not sorted, remove --sorted or sort the file first",
+                    line,
+                    key.join(",")
+                ));
+            }
+        }
+        let mut group = vec![first];
+        while let Some(Ok(row)) = self.rows.peek() {
+            if compare_keys(&key_values(row, self.key), &key).is_ne() {
+                break;
+            }
+            group.push(self.rows.next().unwrap()?);
+        }
+        self.last = Some(key.clone());
+        Ok(Some((key, group)))
+    }
+}
+
+fn key_values(row: &StringRecord, key: &[usize]) -> Vec<String> {
+    key.iter()
+        .map(|&i| row.get(i).unwrap_or_default().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn join(args: &[&str]) -> Result<String> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("join.csv");
+        let output = output.to_str().unwrap();
+        let opts = CsvJoinOpts::parse_from(
+            [
+                "join",
+                "fixtures/csv_diff_old.csv",
+                "fixtures/csv_diff_new.csv",
+                "--key",
+                "ID",
+                "-o",
+                output,
+            ]
+            .iter()
+            .chain(args),
+        );
+        process_csv_join(&opts)?;
+        let content = std::fs::read_to_string(output)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn test_join_kinds() -> Result<()> {
+        let inner = join(&[])?;
+        let mut lines = inner.lines();
+        assert_eq!(
+            lines.next(),
+            Some("ID,Name,Position,Kit Number,Name_right,Position_right,Kit Number_right")
+        );
+        assert_eq!(inner.lines().count(), 4);
+        assert!(inner.contains("3,Gianluigi Buffon,Goalkeeper,77,Gianluigi Buffon,Goalkeeper,1"));
+
+        let left = join(&["--type", "left"])?;
+        assert_eq!(left.lines().count(), 5);
+        assert!(left.contains("2,Mattia Perin,Goalkeeper,37,,,\n"));
+
+        let outer = join(&["--type", "outer"])?;
+        assert_eq!(outer.lines().count(), 6);
+        assert!(outer.contains("4,,,,Carlo Pinsoglio,Goalkeeper,31\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sorted_join_matches_hash_join() -> Result<()> {
+        for kind in ["inner", "left", "outer"] {
+            let mut hash: Vec<String> =
+                join(&["--type", kind])?.lines().map(String::from).collect();
+            let mut sorted: Vec<String> = join(&["--type", kind, "--sorted"])?
+                .lines()
+                .map(String::from)
+                .collect();
+            hash.sort();
+            sorted.sort();
+            assert_eq!(hash, sorted);
+        }
+        Ok(())
+    }
+}
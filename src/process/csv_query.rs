@@ -0,0 +1,273 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use csv::StringRecord;
+use rusqlite::{
+    params_from_iter,
+    types::{Value as SqlValue, ValueRef},
+    Connection,
+};
+use serde_json::Value;
+
+use crate::{
+    cli::{ColumnType, CsvQueryOpts, CsvReaderOpts},
+    get_writer,
+};
+
+use super::{
+    csv_infer::{typed_value, TypeInferer},
+    csv_reader::open_csv,
+    csv_stats::write_table,
+    record_writer::{cell_text, record_writer},
+    sql_writer::sqlite_value,
+};
+
+/// 查询结果 列名和每一行 行中的值按列的顺序排列，列名可以重复
+pub struct QueryResult {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl QueryResult {
+    /// 以列名为键的记录 重复的列名依次加上 _2、_3 等后缀
+    pub fn records(&self) -> impl Iterator<Item = Value> + '_ {
+        let keys = unique_keys(&self.headers);
+        self.rows
+            .iter()
+            .map(move |row| Value::Object(keys.iter().cloned().zip(row.iter().cloned()).collect()))
+    }
+}
+
+/// 执行SQL查询并输出 没有指定格式时打印表格
+pub fn process_csv_query(opts: &CsvQueryOpts) -> Result<()> {
+    let result = query_csv(opts)?;
+    match opts.format {
+        Some(format) => {
+            let mut writer = record_writer(format, &opts.output)?;
+            for record in result.records() {
+                writer.write(&record)?;
+            }
+            writer.finish()
+        }
+        None => {
+            let header: Vec<&str> = result.headers.iter().map(String::as_str).collect();
+            let rows: Vec<Vec<String>> = result
+                .rows
+                .iter()
+                .map(|row| row.iter().map(|v| cell_text(Some(v))).collect())
+                .collect();
+            let mut writer = get_writer(&opts.output)?;
+            write_table(&mut writer, &header, &rows)?;
+            Ok(writer.flush()?)
+        }
+    }
+}
+
+/// 将CSV加载到内存中的SQLite 再执行查询
+pub fn query_csv(opts: &CsvQueryOpts) -> Result<QueryResult> {
+    let mut conn = Connection::open_in_memory()?;
+    for (name, path) in &opts.tables {
+        load_table(&mut conn, name, path, &opts.reader)
+            .map_err(|e| anyhow::anyhow!("Failed to load table {} from {}: {}", name, path, e))?;
+    }
+
+    let mut stmt = conn.prepare(&opts.sql)?;
+    let headers: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = vec![];
+    let mut result = stmt.query([])?;
+    while let Some(row) = result.next()? {
+        let values = (0..headers.len())
+            .map(|i| Ok(json_value(row.get_ref(i)?)))
+            .collect::<Result<_>>()?;
+        rows.push(values);
+    }
+    Ok(QueryResult { headers, rows })
+}
+
+// 推断列类型后建表并写入全部数据
+fn load_table(conn: &mut Connection, name: &str, path: &str, opts: &CsvReaderOpts) -> Result<()> {
+    let (mut reader, headers) = open_csv(path, opts)?;
+
+    // 文件先完整扫描一遍推断类型 标准输入只能缓存全部行
+    let mut inferer = TypeInferer::new(headers.len());
+    let mut buffered = vec![];
+    if path == "-" {
+        for row in reader.records() {
+            let row = row?;
+            inferer.observe(&row);
+            buffered.push(row);
+        }
+    } else {
+        let (mut scan, _) = open_csv(path, opts)?;
+        for row in scan.records() {
+            inferer.observe(&row?);
+        }
+    }
+    let types = inferer.finish();
+
+    let columns = headers
+        .iter()
+        .zip(&types)
+        .map(|(h, ty)| format!("{} {}", quote_ident(h), affinity(*ty)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let placeholders = vec!["?"; headers.len()].join(", ");
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        &format!("CREATE TABLE {} ({})", quote_ident(name), columns),
+        [],
+    )?;
+    {
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} VALUES ({})",
+            quote_ident(name),
+            placeholders
+        ))?;
+        let rows: Box<dyn Iterator<Item = csv::Result<StringRecord>>> = if path == "-" {
+            Box::new(buffered.into_iter().map(Ok))
+        } else {
+            Box::new(reader.records())
+        };
+        for row in rows {
+            let row = row?;
+            let values = headers
+                .iter()
+                .zip(&types)
+                .enumerate()
+                .map(|(i, (header, ty))| {
+                    let cell = row.get(i).unwrap_or_default();
+                    sql_value(cell, *ty).map_err(|e| {
+                        let line = row.position().map(|p| p.line()).unwrap_or_default();
+                        anyhow::anyhow!("line {}, column {}: {}", line, header, e)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            insert.execute(params_from_iter(values))?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+// 列类型对应的SQLite类型 日期时间保存为ISO-8601文本，可直接使用SQLite的日期函数
+fn affinity(ty: ColumnType) -> &'static str {
+    match ty {
+        ColumnType::Int | ColumnType::Bool => "INTEGER",
+        ColumnType::Float => "REAL",
+        _ => "TEXT",
+    }
+}
+
+fn sql_value(cell: &str, ty: ColumnType) -> Result<SqlValue> {
+    Ok(sqlite_value(&typed_value(cell, ty)?))
+}
+
+fn json_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(s) => Value::String(String::from_utf8_lossy(s).into_owned()),
+        // 二进制数据使用Base64表示
+        ValueRef::Blob(b) => Value::String(STANDARD.encode(b)),
+    }
+}
+
+// 重复的列名加上序号 已有的列名不会被占用
+fn unique_keys(headers: &[String]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::with_capacity(headers.len());
+    for header in headers {
+        let mut key = header.clone();
+        let mut n = 1;
+        while keys.contains(&key) || (n > 1 && headers.contains(&key)) {
+            n += 1;
+            key = format!("{}_{}", header, n);
+        }
+        keys.push(key);
+    }
+    keys
+}
+
+// 表名和列名加上双引号 避免与关键字冲突
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use serde_json::json;
+
+    fn query(sql: &str, tables: &[&str]) -> Result<QueryResult> {
+        let mut args = vec!["query", sql];
+        for table in tables {
+            args.extend(["--table", table]);
+        }
+        query_csv(&CsvQueryOpts::parse_from(args))
+    }
+
+    #[test]
+    fn test_query_group_by() -> Result<()> {
+        let result = query(
+            r#"SELECT Position, count(*) AS players, avg("Kit Number") AS kit
+               FROM players GROUP BY Position ORDER BY players DESC, Position"#,
+            &["players=assets/juventus.csv"],
+        )?;
+        assert_eq!(result.headers, vec!["Position", "players", "kit"]);
+        assert_eq!(result.rows.len(), 10);
+        assert_eq!(result.rows[0][0], json!("Central Midfield"));
+        assert_eq!(result.rows[0][1], json!(6));
+        // 推断为整数后 数值聚合才能正确计算
+        assert!(result.rows[0][2].is_f64());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_join() -> Result<()> {
+        let result = query(
+            r#"SELECT n.Continent, count(*) AS players FROM juventus p
+               JOIN nations n ON n.Nationality = p.Nationality
+               GROUP BY n.Continent ORDER BY n.Continent"#,
+            &["assets/juventus.csv", "nations=fixtures/csv_nations.csv"],
+        )?;
+        assert_eq!(
+            result.records().collect::<Vec<_>>(),
+            vec![
+                json!({"Continent": "Europe", "players": 10}),
+                json!({"Continent": "South America", "players": 5}),
+            ]
+        );
+        assert!(query("SELECT * FROM missing", &["assets/juventus.csv"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_duplicate_column_names() -> Result<()> {
+        // 两个表的同名列都要保留 不能互相覆盖
+        let result = query(
+            r#"SELECT a.Name, b.Name, a.Name AS Name_2 FROM a
+               JOIN b ON b."Kit Number" = a."Kit Number" + 1
+               ORDER BY a."Kit Number" LIMIT 1"#,
+            &["a=assets/juventus.csv", "b=assets/juventus.csv"],
+        )?;
+        assert_eq!(result.headers, vec!["Name", "Name", "Name_2"]);
+        assert_eq!(
+            result.rows,
+            vec![vec![
+                json!("Wojciech Szczesny"),
+                json!("Mattia De Sciglio"),
+                json!("Wojciech Szczesny")
+            ]]
+        );
+        assert_eq!(
+            result.records().next(),
+            Some(json!({
+                "Name": "Wojciech Szczesny",
+                "Name_3": "Mattia De Sciglio",
+                "Name_2": "Wojciech Szczesny",
+            }))
+        );
+        Ok(())
+    }
+}
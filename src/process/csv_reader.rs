@@ -0,0 +1,194 @@
+use std::{
+    fmt,
+    io::{Cursor, Read},
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+
+use crate::{cli::CsvReaderOpts, get_reader};
+
+use super::{
+    encoding::decode_reader,
+    excel::{is_workbook, read_sheet},
+};
+
+/// CSV Reader 输入可以是文件或者标准输入
+pub type CsvReader = csv::Reader<Box<dyn Read>>;
+
+/// 根据读取选项打开CSV 返回Reader和列名
+pub fn open_csv(input: &str, opts: &CsvReaderOpts) -> Result<(CsvReader, StringRecord)> {
+    open_with(input, opts, false)
+}
+
+/// 打开CSV 但不检查每行的列数，由调用方通过 check_width 检查并保留出错的行
+pub fn open_csv_flexible(input: &str, opts: &CsvReaderOpts) -> Result<(CsvReader, StringRecord)> {
+    open_with(input, opts, true)
+}
+
+fn open_with(
+    input: &str,
+    opts: &CsvReaderOpts,
+    flexible: bool,
+) -> Result<(CsvReader, StringRecord)> {
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .has_headers(opts.header)
+        .trim(opts.trim.into())
+        .flexible(flexible);
+    // 工作簿先转换为CSV文本 分隔符和引号等选项不再适用
+    if is_workbook(input) {
+        let data = read_sheet(input, opts.sheet.as_deref(), opts.range.as_deref())?;
+        let mut reader = builder.from_reader(Box::new(Cursor::new(data)) as Box<dyn Read>);
+        let headers = read_headers(&mut reader, opts)?;
+        return Ok((reader, headers));
+    }
+    if opts.sheet.is_some() || opts.range.is_some() {
+        return Err(anyhow::anyhow!(
+            "--sheet and --range only apply to .xlsx, .xls and .ods input"
+        ));
+    }
+    builder
+        .delimiter(opts.delimiter as u8)
+        .quote(opts.quote as u8)
+        .quoting(!opts.no_quoting)
+        .comment(opts.comment.map(|c| c as u8));
+    // 指定了转义字符时 不再使用双引号转义
+    if let Some(escape) = opts.escape {
+        builder.escape(Some(escape as u8)).double_quote(false);
+    }
+
+    let mut reader = builder.from_reader(decode_reader(get_reader(input)?, opts.encoding)?);
+    let headers = read_headers(&mut reader, opts)?;
+    Ok((reader, headers))
+}
+
+/// 获取列名 优先使用 --columns，没有Header时生成 col_1,col_2...
+fn read_headers(reader: &mut CsvReader, opts: &CsvReaderOpts) -> Result<StringRecord> {
+    // 没有Header时 headers()返回的是第一行数据，只用来确定列数
+    let width = reader.headers()?.len();
+
+    let headers = match &opts.columns {
+        Some(columns) => {
+            if columns.len() != width {
+                return Err(anyhow::anyhow!(
+                    "--columns has {} names but the file has {} columns",
+                    columns.len(),
+                    width
+                ));
+            }
+            StringRecord::from(columns.clone())
+        }
+        None if opts.header => reader.headers()?.clone(),
+        None => (1..=width).map(|i| format!("col_{}", i)).collect(),
+    };
+
+    Ok(headers)
+}
+
+/// 查找列的下标 优先按列名匹配，其次按从1开始的序号
+pub fn column_index(headers: &StringRecord, column: &str) -> Result<usize> {
+    if let Some(idx) = headers.iter().position(|h| h == column) {
+        return Ok(idx);
+    }
+    match column.parse::<usize>() {
+        Ok(n) if (1..=headers.len()).contains(&n) => Ok(n - 1),
+        _ => Err(anyhow::anyhow!("Unknown column: {}", column)),
+    }
+}
+
+/// 单行数据的错误 宽松模式和校验时记录后继续读取
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    pub line: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for RowError {}
+
+/// 检查一行的列数是否与Header一致
+pub fn check_width(record: &StringRecord, width: usize) -> Result<(), RowError> {
+    if record.len() != width {
+        return Err(RowError {
+            line: record.position().map(|p| p.line()).unwrap_or_default(),
+            reason: format!("expected {} fields, found {}", width, record.len()),
+        });
+    }
+    Ok(())
+}
+
+/// 将读取错误转换为行错误 IO错误无法继续读取，直接返回
+pub fn row_error(err: csv::Error) -> Result<RowError> {
+    let line = err.position().map(|p| p.line()).unwrap_or_default();
+    let reason = match err.kind() {
+        csv::ErrorKind::UnequalLengths {
+            expected_len, len, ..
+        } => format!("expected {} fields, found {}", expected_len, len),
+        csv::ErrorKind::Utf8 { err, .. } => format!("invalid UTF-8 in field {}", err.field() + 1),
+        _ if err.is_io_error() => return Err(err.into()),
+        _ => err.to_string(),
+    };
+    Ok(RowError { line, reason })
+}
+
+/// 将读取错误转换为带行号的错误
+pub fn record_error(err: csv::Error) -> anyhow::Error {
+    match row_error(err) {
+        Ok(e) => e.into(),
+        Err(e) => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_open_csv_semicolon() -> Result<()> {
+        let opts = CsvReaderOpts::parse_from(["csv", "-d", ";"]);
+        let (mut reader, headers) = open_csv("fixtures/csv_semicolon.csv", &opts)?;
+        assert_eq!(headers, vec!["Name", "City", "Score"]);
+        assert_eq!(reader.records().count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_csv_headerless() -> Result<()> {
+        let opts = CsvReaderOpts::parse_from(["csv", "-d", "\\t", "--header", "false"]);
+        let (mut reader, headers) = open_csv("fixtures/csv_headerless.tsv", &opts)?;
+        assert_eq!(headers, vec!["col_1", "col_2", "col_3"]);
+        // 第一行也是数据
+        assert_eq!(reader.records().count(), 3);
+
+        let opts = CsvReaderOpts::parse_from([
+            "csv",
+            "-d",
+            "\\t",
+            "--header",
+            "false",
+            "--columns",
+            "name,city,score",
+        ]);
+        let (_, headers) = open_csv("fixtures/csv_headerless.tsv", &opts)?;
+        assert_eq!(headers, vec!["name", "city", "score"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_index() {
+        let headers = StringRecord::from(vec!["a", "2", "c"]);
+        assert_eq!(column_index(&headers, "c").unwrap(), 2);
+        // 列名优先于序号
+        assert_eq!(column_index(&headers, "2").unwrap(), 1);
+        assert_eq!(column_index(&headers, "3").unwrap(), 2);
+        assert!(column_index(&headers, "0").is_err());
+        assert!(column_index(&headers, "d").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+
+use crate::cli::CsvSplitOpts;
+
+use super::{
+    csv_reader::{column_index, open_csv},
+    excel::is_workbook,
+};
+
+/// 按列拆分时同时打开的最大文件数
+const MAX_OPEN_FILES: usize = 128;
+
+/// 拆分CSV 每个文件都带Header，返回生成的文件
+pub fn process_csv_split(opts: &CsvSplitOpts) -> Result<Vec<PathBuf>> {
+    let (mut reader, headers) = open_csv(&opts.input, &opts.reader)?;
+    let input = Path::new(&opts.input);
+    let prefix = match &opts.prefix {
+        Some(prefix) => prefix.clone(),
+        None if opts.input == "-" => "data".to_string(),
+        None => input
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "data".to_string()),
+    };
+    // 工作簿拆分后也输出CSV
+    let extension = match input.extension() {
+        Some(ext) if opts.input != "-" && !is_workbook(&opts.input) => {
+            ext.to_string_lossy().to_string()
+        }
+        _ => "csv".to_string(),
+    };
+    let mut files = SplitFiles::new(&opts.output_dir, headers.clone(), opts.reader.delimiter);
+
+    if let Some(rows) = opts.rows {
+        let mut part = 0;
+        let mut count = rows;
+        for row in reader.records() {
+            let row = row?;
+            if count == rows {
+                files.close_all()?;
+                part += 1;
+                count = 0;
+            }
+            let name = format!("{}_{:04}.{}", prefix, part, extension);
+            files.write(&name, &row)?;
+            count += 1;
+        }
+    } else if let Some(by) = &opts.by {
+        let idx = column_index(&headers, by)?;
+        // 列值到文件名的映射 清理后重名的加上序号
+        let mut names: HashMap<String, String> = HashMap::new();
+        let mut used: HashSet<String> = HashSet::new();
+        for row in reader.records() {
+            let row = row?;
+            let value = row.get(idx).unwrap_or_default();
+            if !names.contains_key(value) {
+                let base = format!("{}_{}", prefix, sanitize(value));
+                let mut name = format!("{}.{}", base, extension);
+                let mut n = 1;
+                while !used.insert(name.clone()) {
+                    n += 1;
+                    name = format!("{}_{}.{}", base, n, extension);
+                }
+                names.insert(value.to_string(), name);
+            }
+            files.write(&names[value], &row)?;
+        }
+    }
+    files.finish()
+}
+
+// 文件名中只保留字母、数字和 _.- 其余替换为_
+fn sanitize(value: &str) -> String {
+    if value.is_empty() {
+        return "empty".to_string();
+    }
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// 拆分输出的文件 打开的文件过多时全部关闭，之后以追加方式重新打开
+struct SplitFiles<'a> {
+    dir: &'a Path,
+    headers: StringRecord,
+    delimiter: u8,
+    open: HashMap<String, csv::Writer<File>>,
+    created: Vec<PathBuf>,
+    seen: HashSet<String>,
+}
+
+impl<'a> SplitFiles<'a> {
+    fn new(dir: &'a Path, headers: StringRecord, delimiter: char) -> Self {
+        Self {
+            dir,
+            headers,
+            delimiter: delimiter as u8,
+            open: HashMap::new(),
+            created: vec![],
+            seen: HashSet::new(),
+        }
+    }
+
+    fn write(&mut self, name: &str, row: &StringRecord) -> Result<()> {
+        if !self.open.contains_key(name) {
+            if self.open.len() >= MAX_OPEN_FILES {
+                self.close_all()?;
+            }
+            let path = self.dir.join(name);
+            let writer = if self.seen.insert(name.to_string()) {
+                let mut writer = self.builder().from_path(&path)?;
+                writer.write_record(&self.headers)?;
+                self.created.push(path);
+                writer
+            } else {
+                let file = OpenOptions::new().append(true).open(&path)?;
+                self.builder().from_writer(file)
+            };
+            self.open.insert(name.to_string(), writer);
+        }
+        self.open.get_mut(name).unwrap().write_record(row)?;
+        Ok(())
+    }
+
+    fn builder(&self) -> csv::WriterBuilder {
+        let mut builder = csv::WriterBuilder::new();
+        builder.delimiter(self.delimiter).has_headers(false);
+        builder
+    }
+
+    fn close_all(&mut self) -> Result<()> {
+        for (_, mut writer) in self.open.drain() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.close_all()?;
+        Ok(self.created)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn split(args: &[&str]) -> Result<(TempDir, Vec<PathBuf>)> {
+        let dir = tempfile::tempdir()?;
+        let opts = CsvSplitOpts::parse_from(
+            [
+                "split",
+                "-i",
+                "assets/juventus.csv",
+                "-o",
+                dir.path().to_str().unwrap(),
+            ]
+            .iter()
+            .chain(args),
+        );
+        let files = process_csv_split(&opts)?;
+        Ok((dir, files))
+    }
+
+    #[test]
+    fn test_split_by_rows() -> Result<()> {
+        let (_dir, files) = split(&["--rows", "10"])?;
+        let total = std::fs::read_to_string("assets/juventus.csv")?
+            .lines()
+            .count()
+            - 1;
+        assert_eq!(files.len(), total.div_ceil(10));
+        assert!(files[0].ends_with("juventus_0001.csv"));
+        let mut rows = 0;
+        for file in &files {
+            let content = std::fs::read_to_string(file)?;
+            assert!(content.starts_with("Name,"));
+            rows += content.lines().count() - 1;
+        }
+        assert_eq!(rows, total);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_by_column() -> Result<()> {
+        let (dir, files) = split(&["--by", "Position", "--prefix", "pos"])?;
+        let goalkeepers = std::fs::read_to_string(dir.path().join("pos_Goalkeeper.csv"))?;
+        assert_eq!(goalkeepers.lines().count(), 1 + 4);
+        assert!(files.iter().any(|f| f.ends_with("pos_Centre-Back.csv")));
+        assert_eq!(sanitize("a/b c"), "a_b_c");
+        assert_eq!(sanitize(""), "empty");
+        Ok(())
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use rand::Rng;
+use serde::Serialize;
+use unicode_width::UnicodeWidthStr;
+
+use crate::cli::{ColumnType, CsvStatsOpts};
+
+use super::{
+    csv_infer::{detect_type, merge_type},
+    csv_reader::open_csv,
+    record_writer::record_writer,
+};
+
+/// 精确统计不同值个数的上限 超过后改用HyperLogLog估算
+const DISTINCT_EXACT_LIMIT: usize = 65_536;
+/// 计算中位数时保留的样本数 超过后使用蓄水池抽样估算
+const MEDIAN_SAMPLE_SIZE: usize = 100_000;
+/// HyperLogLog的精度 2^14个寄存器 误差约0.8%
+const HLL_PRECISION: u32 = 14;
+
+/// 单列的统计结果
+#[derive(Debug, Serialize)]
+pub struct ColumnStats {
+    /// 列名
+    pub column: String,
+    /// 推断的类型
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// 总行数
+    pub rows: u64,
+    /// 空值个数
+    pub nulls: u64,
+    /// 不同值个数
+    pub distinct: u64,
+    /// 不同值个数是否为估算值
+    pub distinct_approx: bool,
+    /// 最小值 数字列按数值比较，其余按字符串比较
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<String>,
+    /// 最大值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<String>,
+    /// 平均值 仅数字列
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean: Option<f64>,
+    /// 中位数 仅数字列
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median: Option<f64>,
+    /// 标准差 仅数字列
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stddev: Option<f64>,
+    /// 最短长度 仅字符串列
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    /// 最长长度 仅字符串列
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// 出现次数最多的值
+    pub top: Vec<TopValue>,
+}
+
+/// 高频值
+#[derive(Debug, Serialize)]
+pub struct TopValue {
+    pub value: String,
+    pub count: u64,
+}
+
+/// 统计CSV每一列 只遍历一次且内存占用有上限
+pub fn process_csv_stats(opts: &CsvStatsOpts) -> Result<Vec<ColumnStats>> {
+    let (mut reader, headers) = open_csv(&opts.input, &opts.reader)?;
+    let mut collector = StatsCollector::new(headers.len(), opts.top);
+    for row in reader.records() {
+        collector.observe(&row?);
+    }
+    Ok(collector.finish(&headers))
+}
+
+/// 逐行累积每一列的统计 供其他输出复用
+pub struct StatsCollector {
+    columns: Vec<ColumnProfile>,
+    top: usize,
+}
+
+impl StatsCollector {
+    pub fn new(width: usize, top: usize) -> Self {
+        Self {
+            columns: (0..width).map(|_| ColumnProfile::new(top)).collect(),
+            top,
+        }
+    }
+
+    /// 观察一行 多出的单元格被忽略
+    pub fn observe<'a>(&mut self, cells: impl IntoIterator<Item = &'a str>) {
+        for (profile, cell) in self.columns.iter_mut().zip(cells) {
+            profile.observe(cell);
+        }
+    }
+
+    /// 得到每一列的统计结果
+    pub fn finish<'a>(self, headers: impl IntoIterator<Item = &'a str>) -> Vec<ColumnStats> {
+        headers
+            .into_iter()
+            .zip(self.columns)
+            .map(|(name, profile)| profile.finish(name, self.top))
+            .collect()
+    }
+}
+
+/// 输出统计结果 没有指定格式时打印表格
+pub fn write_csv_stats(stats: &[ColumnStats], opts: &CsvStatsOpts) -> Result<()> {
+    match opts.format {
+        Some(format) => {
+            let mut writer = record_writer(format, &opts.output)?;
+            for column in stats {
+                writer.write(&serde_json::to_value(column)?)?;
+            }
+            writer.finish()
+        }
+        None => {
+            let header = [
+                "column", "type", "rows", "nulls", "distinct", "min", "max", "mean", "median",
+                "stddev", "len", "top",
+            ];
+            let rows: Vec<Vec<String>> = stats.iter().map(table_row).collect();
+            let mut writer = crate::get_writer(&opts.output)?;
+            write_table(&mut writer, &header, &rows)?;
+            Ok(writer.flush()?)
+        }
+    }
+}
+
+// 统计结果转换为表格的一行
+fn table_row(s: &ColumnStats) -> Vec<String> {
+    let float = |v: Option<f64>| v.map(|v| format!("{:.2}", v)).unwrap_or_default();
+    let len = match (s.min_length, s.max_length) {
+        (Some(min), Some(max)) => format!("{}-{}", min, max),
+        _ => String::new(),
+    };
+    let top = s
+        .top
+        .iter()
+        .map(|t| format!("{} ({})", t.value, t.count))
+        .collect::<Vec<_>>()
+        .join(", ");
+    vec![
+        s.column.clone(),
+        s.ty.clone(),
+        s.rows.to_string(),
+        s.nulls.to_string(),
+        format!("{}{}", if s.distinct_approx { "~" } else { "" }, s.distinct),
+        s.min.clone().unwrap_or_default(),
+        s.max.clone().unwrap_or_default(),
+        float(s.mean),
+        float(s.median),
+        float(s.stddev),
+        len,
+        top,
+    ]
+}
+
+/// 输出对齐的表格
+pub fn write_table(
+    writer: &mut impl std::io::Write,
+    header: &[&str],
+    rows: &[Vec<String>],
+) -> Result<()> {
+    for line in format_table(header, rows) {
+        writeln!(writer, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// 格式化对齐的表格 返回表头、分隔线和每一行
+pub fn format_table(header: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.width()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{}{}", c, " ".repeat(w - c.width())))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut lines = vec![
+        line(header.to_vec()),
+        widths
+            .iter()
+            .map(|w| "-".repeat(*w))
+            .collect::<Vec<_>>()
+            .join("  "),
+    ];
+    lines.extend(
+        rows.iter()
+            .map(|row| line(row.iter().map(String::as_str).collect())),
+    );
+    lines
+}
+
+/// 单列的累积状态
+struct ColumnProfile {
+    ty: ColumnType,
+    rows: u64,
+    nulls: u64,
+    distinct: DistinctCounter,
+    frequent: FrequentValues,
+    numeric: NumericStats,
+    min_str: Option<String>,
+    max_str: Option<String>,
+    min_len: Option<usize>,
+    max_len: Option<usize>,
+}
+
+impl ColumnProfile {
+    fn new(top: usize) -> Self {
+        Self {
+            ty: ColumnType::Null,
+            rows: 0,
+            nulls: 0,
+            distinct: DistinctCounter::new(),
+            frequent: FrequentValues::new((top * 50).max(1000)),
+            numeric: NumericStats::new(),
+            min_str: None,
+            max_str: None,
+            min_len: None,
+            max_len: None,
+        }
+    }
+
+    fn observe(&mut self, cell: &str) {
+        self.rows += 1;
+        let ty = detect_type(cell);
+        self.ty = merge_type(self.ty, ty);
+        if ty == ColumnType::Null {
+            self.nulls += 1;
+            return;
+        }
+
+        self.distinct.insert(cell);
+        self.frequent.insert(cell);
+        if matches!(ty, ColumnType::Int | ColumnType::Float) {
+            if let Ok(v) = cell.parse::<f64>() {
+                self.numeric.push(v);
+            }
+        }
+        if self.min_str.as_deref().is_none_or(|m| cell < m) {
+            self.min_str = Some(cell.to_string());
+        }
+        if self.max_str.as_deref().is_none_or(|m| cell > m) {
+            self.max_str = Some(cell.to_string());
+        }
+        let len = cell.chars().count();
+        self.min_len = Some(self.min_len.map_or(len, |m| m.min(len)));
+        self.max_len = Some(self.max_len.map_or(len, |m| m.max(len)));
+    }
+
+    fn finish(self, column: &str, top: usize) -> ColumnStats {
+        let numeric = matches!(self.ty, ColumnType::Int | ColumnType::Float);
+        let is_string = self.ty == ColumnType::String;
+        let (distinct, distinct_approx) = self.distinct.count();
+        let (min, max) = if numeric {
+            (
+                self.numeric.min.map(|v| v.to_string()),
+                self.numeric.max.map(|v| v.to_string()),
+            )
+        } else {
+            (self.min_str, self.max_str)
+        };
+        ColumnStats {
+            column: column.to_string(),
+            ty: self.ty.to_string(),
+            rows: self.rows,
+            nulls: self.nulls,
+            distinct,
+            distinct_approx,
+            min,
+            max,
+            mean: numeric.then_some(self.numeric.mean).flatten(),
+            stddev: numeric.then(|| self.numeric.stddev()).flatten(),
+            median: numeric.then(|| self.numeric.median()).flatten(),
+            min_length: is_string.then_some(self.min_len).flatten(),
+            max_length: is_string.then_some(self.max_len).flatten(),
+            top: self.frequent.top(top),
+        }
+    }
+}
+
+/// 不同值计数 先精确统计，超过上限后转为HyperLogLog
+enum DistinctCounter {
+    Exact(HashSet<String>),
+    Approx(HyperLogLog),
+}
+
+impl DistinctCounter {
+    fn new() -> Self {
+        Self::Exact(HashSet::new())
+    }
+
+    fn insert(&mut self, value: &str) {
+        match self {
+            Self::Exact(set) => {
+                if !set.contains(value) {
+                    set.insert(value.to_string());
+                }
+                if set.len() > DISTINCT_EXACT_LIMIT {
+                    let mut hll = HyperLogLog::new(HLL_PRECISION);
+                    set.iter().for_each(|v| hll.insert(v));
+                    *self = Self::Approx(hll);
+                }
+            }
+            Self::Approx(hll) => hll.insert(value),
+        }
+    }
+
+    fn count(&self) -> (u64, bool) {
+        match self {
+            Self::Exact(set) => (set.len() as u64, false),
+            Self::Approx(hll) => (hll.estimate(), true),
+        }
+    }
+}
+
+/// HyperLogLog 基数估算
+pub struct HyperLogLog {
+    precision: u32,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub fn new(precision: u32) -> Self {
+        Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    pub fn insert(&mut self, value: &str) {
+        let hash = blake3::hash(value.as_bytes());
+        let hash = u64::from_le_bytes(hash.as_bytes()[..8].try_into().expect("8 bytes"));
+        // 高位作为寄存器下标 剩余位计算前导零
+        let idx = (hash >> (64 - self.precision)) as usize;
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[idx] = self.registers[idx].max(rank);
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // 基数较小时使用线性计数修正
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// 高频值统计 使用Misra-Gries算法限制内存，未发生淘汰时计数是精确的
+struct FrequentValues {
+    capacity: usize,
+    counts: HashMap<String, u64>,
+}
+
+impl FrequentValues {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counts: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        if let Some(count) = self.counts.get_mut(value) {
+            *count += 1;
+        } else if self.counts.len() < self.capacity {
+            self.counts.insert(value.to_string(), 1);
+        } else {
+            // 所有计数减一 淘汰归零的值
+            self.counts.retain(|_, count| {
+                *count -= 1;
+                *count > 0
+            });
+        }
+    }
+
+    fn top(&self, n: usize) -> Vec<TopValue> {
+        let mut values: Vec<_> = self.counts.iter().collect();
+        values.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        values
+            .into_iter()
+            .take(n)
+            .map(|(value, count)| TopValue {
+                value: value.clone(),
+                count: *count,
+            })
+            .collect()
+    }
+}
+
+/// 数值统计 使用Welford算法计算均值和方差
+struct NumericStats {
+    count: u64,
+    mean: Option<f64>,
+    m2: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    sample: Vec<f64>,
+}
+
+impl NumericStats {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            mean: None,
+            m2: 0.0,
+            min: None,
+            max: None,
+            sample: vec![],
+        }
+    }
+
+    fn push(&mut self, v: f64) {
+        self.count += 1;
+        let mean = self.mean.unwrap_or(0.0);
+        let delta = v - mean;
+        let mean = mean + delta / self.count as f64;
+        self.m2 += delta * (v - mean);
+        self.mean = Some(mean);
+        self.min = Some(self.min.map_or(v, |m| m.min(v)));
+        self.max = Some(self.max.map_or(v, |m| m.max(v)));
+
+        // 蓄水池抽样
+        if self.sample.len() < MEDIAN_SAMPLE_SIZE {
+            self.sample.push(v);
+        } else {
+            let idx = rand::thread_rng().gen_range(0..self.count) as usize;
+            if idx < MEDIAN_SAMPLE_SIZE {
+                self.sample[idx] = v;
+            }
+        }
+    }
+
+    fn stddev(&self) -> Option<f64> {
+        (self.count > 1).then(|| (self.m2 / (self.count - 1) as f64).sqrt())
+    }
+
+    fn median(&self) -> Option<f64> {
+        let mut sample = self.sample.clone();
+        sample.sort_by(f64::total_cmp);
+        let n = sample.len();
+        match n {
+            0 => None,
+            n if n % 2 == 1 => Some(sample[n / 2]),
+            n => Some((sample[n / 2 - 1] + sample[n / 2]) / 2.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_csv_stats() -> Result<()> {
+        let opts = CsvStatsOpts::parse_from(["stats", "-i", "assets/juventus.csv", "--top", "2"]);
+        let stats = process_csv_stats(&opts)?;
+        assert_eq!(stats.len(), 5);
+
+        let nationality = &stats[3];
+        assert_eq!(nationality.ty, "string");
+        assert_eq!(nationality.rows, 27);
+        assert_eq!(nationality.top[0].value, "Italy");
+        assert!(nationality.min_length.is_some());
+
+        let kit = &stats[4];
+        assert_eq!(kit.ty, "int");
+        assert_eq!(kit.distinct, 27);
+        assert!(!kit.distinct_approx);
+        assert_eq!(kit.min.as_deref(), Some("1"));
+        assert!(kit.mean.is_some() && kit.median.is_some() && kit.stddev.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_stats() {
+        let mut stats = NumericStats::new();
+        [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+            .into_iter()
+            .for_each(|v| stats.push(v));
+        assert_eq!(stats.mean, Some(5.0));
+        assert_eq!(stats.median(), Some(4.5));
+        assert!((stats.stddev().unwrap() - 2.138).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_hyperloglog() {
+        let mut hll = HyperLogLog::new(HLL_PRECISION);
+        for i in 0..200_000 {
+            hll.insert(&i.to_string());
+        }
+        let estimate = hll.estimate() as f64;
+        assert!((estimate - 200_000.0).abs() / 200_000.0 < 0.03);
+    }
+}
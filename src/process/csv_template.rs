@@ -0,0 +1,223 @@
+use std::{
+    io::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+use serde_json::Value;
+use tera::{Context, Tera};
+
+use crate::get_writer;
+
+use super::{
+    csv_stats::StatsCollector,
+    record_writer::{cell_text, RecordWriter},
+};
+
+/// 文件名模板的名称
+const FILENAME_TEMPLATE: &str = "__filename";
+/// 模板中统计的高频值个数
+const STATS_TOP: usize = 5;
+
+/// 使用Tera模板渲染记录
+/// 默认收集全部记录在finish时渲染一次，逐行模式下每条记录渲染为一个文件
+pub struct TemplateWriter {
+    tera: Tera,
+    name: String,
+    headers: Vec<String>,
+    output: String,
+    mode: RenderMode,
+}
+
+enum RenderMode {
+    /// 渲染全部记录 同时统计每一列
+    All {
+        records: Vec<Value>,
+        stats: StatsCollector,
+    },
+    /// 每条记录一个文件
+    PerRow { dir: PathBuf, count: usize },
+}
+
+impl TemplateWriter {
+    /// 加载模板 模板名去掉.tera后缀，.html等后缀的模板自动转义
+    pub fn new(
+        template: &str,
+        per_row: Option<&str>,
+        headers: &StringRecord,
+        output: &str,
+    ) -> Result<Self> {
+        let file = Path::new(template)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = file.strip_suffix(".tera").unwrap_or(&file).to_string();
+        let mut tera = Tera::default();
+        tera.add_raw_template(&name, &std::fs::read_to_string(template)?)?;
+
+        let mode = match per_row {
+            Some(filename) => {
+                if output == "-" {
+                    return Err(anyhow::anyhow!("--per-row requires an output directory"));
+                }
+                tera.add_raw_template(FILENAME_TEMPLATE, filename)?;
+                std::fs::create_dir_all(output)?;
+                RenderMode::PerRow {
+                    dir: PathBuf::from(output),
+                    count: 0,
+                }
+            }
+            None => RenderMode::All {
+                records: vec![],
+                stats: StatsCollector::new(headers.len(), STATS_TOP),
+            },
+        };
+        Ok(Self {
+            tera,
+            name,
+            headers: headers.iter().map(String::from).collect(),
+            output: output.to_string(),
+            mode,
+        })
+    }
+}
+
+impl RecordWriter for TemplateWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        match &mut self.mode {
+            RenderMode::All { records, stats } => {
+                let cells: Vec<String> = self
+                    .headers
+                    .iter()
+                    .map(|h| cell_text(record.get(h)))
+                    .collect();
+                stats.observe(cells.iter().map(String::as_str));
+                records.push(record.clone());
+            }
+            RenderMode::PerRow { dir, count } => {
+                *count += 1;
+                let mut context = Context::new();
+                context.insert("record", record);
+                context.insert("index", count);
+                context.insert("headers", &self.headers);
+                let filename = self.tera.render(FILENAME_TEMPLATE, &context)?;
+                let path = dir.join(safe_filename(filename.trim())?);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, self.tera.render(&self.name, &context)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let mode = std::mem::replace(
+            &mut self.mode,
+            RenderMode::PerRow {
+                dir: PathBuf::new(),
+                count: 0,
+            },
+        );
+        match mode {
+            RenderMode::All { records, stats } => {
+                let mut context = Context::new();
+                context.insert("headers", &self.headers);
+                context.insert("count", &records.len());
+                context.insert(
+                    "stats",
+                    &stats.finish(self.headers.iter().map(String::as_str)),
+                );
+                context.insert("records", &records);
+                let mut writer = get_writer(&self.output)?;
+                writer.write_all(self.tera.render(&self.name, &context)?.as_bytes())?;
+                writer.flush()?;
+            }
+            RenderMode::PerRow { count, .. } => {
+                tracing::info!("渲染了 {} 个文件", count);
+            }
+        }
+        Ok(())
+    }
+}
+
+// 渲染出的文件名只能是输出目录下的相对路径
+fn safe_filename(filename: &str) -> Result<&Path> {
+    let path = Path::new(filename);
+    let valid = !filename.is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid file name {:?} rendered by --per-row",
+            filename
+        ));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cli::CsvOpts, process_csv};
+    use clap::Parser;
+
+    #[test]
+    fn test_render_all_records() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("report.md");
+        let output = output.to_str().unwrap();
+        let opts = CsvOpts::parse_from([
+            "csv",
+            "-i",
+            "assets/juventus.csv",
+            "--infer",
+            "--where",
+            "Position == \"Goalkeeper\"",
+            "--select",
+            "Name,Kit Number",
+            "--template",
+            "fixtures/template_report.md.tera",
+        ]);
+        process_csv(&opts, output)?;
+        let content = std::fs::read_to_string(output)?;
+
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[0], "# 4 players");
+        assert_eq!(lines[2], "| Name | Kit Number |");
+        assert_eq!(lines[4], "| Wojciech Szczesny | 1 |");
+        assert_eq!(lines.last(), Some(&"Kit Number: int, 1 - 77"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_per_row() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let dir = temp.path().join("rows");
+        let opts = CsvOpts::parse_from([
+            "csv",
+            "-i",
+            "fixtures/csv_diff_old.csv",
+            "--template",
+            "fixtures/template_player.html.tera",
+            "--per-row",
+            "{{ index }}-{{ record.Name | slugify }}.html",
+        ]);
+        process_csv(&opts, dir.to_str().unwrap())?;
+        let content = std::fs::read_to_string(dir.join("1-wojciech-szczesny.html"))?;
+        // .html模板自动转义
+        assert_eq!(
+            content.trim(),
+            "<h1>Wojciech Szczesny</h1>\n<p>ID &amp; Name &amp; Position &amp; Kit Number</p>"
+        );
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 4);
+
+        assert!(safe_filename("../x.md").is_err());
+        assert!(safe_filename("/tmp/x.md").is_err());
+        assert!(safe_filename("").is_err());
+        assert!(safe_filename("a/b.md").is_ok());
+        Ok(())
+    }
+}
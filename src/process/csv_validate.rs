@@ -0,0 +1,330 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    cli::{ColumnType, CsvValidateOpts, ReportFormat},
+    get_reader, get_writer,
+};
+
+use super::{
+    csv_infer::{is_null, typed_value},
+    csv_reader::{check_width, open_csv_flexible, row_error},
+};
+
+/// Schema文件 YAML或Json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemaFile {
+    columns: Vec<ColumnSchema>,
+}
+
+/// 单列的约束
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnSchema {
+    name: String,
+    /// 列类型 与 --types 相同
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    /// 文件中必须有该列
+    #[serde(default = "default_true")]
+    required: bool,
+    /// 是否允许空值
+    #[serde(default = "default_true")]
+    nullable: bool,
+    /// 正则 需要完整匹配时请加上 ^ 和 $
+    pattern: Option<String>,
+    /// 允许的值
+    #[serde(rename = "enum")]
+    values: Option<Vec<String>>,
+    min: Option<Bound>,
+    max: Option<Bound>,
+    #[serde(default)]
+    unique: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 范围 数字对数值列比较数值，对其他列比较字符数；字符串按转换后的值比较，如日期
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Bound {
+    Number(f64),
+    Text(String),
+}
+
+/// 一条违规
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    pub reason: String,
+}
+
+/// 校验结果
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    /// 数据行数
+    pub rows: usize,
+    /// 有违规的行数
+    pub invalid_rows: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// 编译后的列规则
+struct ColumnRule {
+    name: String,
+    index: usize,
+    ty: ColumnType,
+    nullable: bool,
+    pattern: Option<Regex>,
+    values: Option<Vec<String>>,
+    min: Option<Bound>,
+    max: Option<Bound>,
+    /// 唯一列已出现的值及其行号
+    seen: Option<HashMap<String, u64>>,
+}
+
+/// 校验并输出报告
+pub fn process_csv_validate(opts: &CsvValidateOpts) -> Result<ValidationReport> {
+    let report = validate_csv(opts)?;
+    let mut writer = get_writer(&opts.output)?;
+    match opts.format {
+        ReportFormat::Text => {
+            for v in &report.violations {
+                match &v.column {
+                    Some(column) => {
+                        writeln!(writer, "line {}, column {}: {}", v.line, column, v.reason)?
+                    }
+                    None => writeln!(writer, "line {}: {}", v.line, v.reason)?,
+                }
+            }
+            writeln!(
+                writer,
+                "{} violations in {} of {} rows",
+                report.violations.len(),
+                report.invalid_rows,
+                report.rows
+            )?;
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writeln!(writer)?;
+        }
+    }
+    writer.flush()?;
+    Ok(report)
+}
+
+/// 校验所有行 收集全部违规而不是在第一处停止
+pub fn validate_csv(opts: &CsvValidateOpts) -> Result<ValidationReport> {
+    let mut schema = String::new();
+    get_reader(&opts.schema)?.read_to_string(&mut schema)?;
+    let schema: SchemaFile =
+        serde_yaml::from_str(&schema).map_err(|e| anyhow::anyhow!("Invalid schema: {}", e))?;
+
+    let (mut reader, headers) = open_csv_flexible(&opts.input, &opts.reader)?;
+    let mut report = ValidationReport::default();
+    let mut rules = vec![];
+    for column in schema.columns {
+        match headers.iter().position(|h| h == column.name) {
+            Some(index) => rules.push(compile_rule(column, index)?),
+            None if column.required => report.violations.push(Violation {
+                line: 1,
+                column: Some(column.name),
+                reason: "missing required column".to_string(),
+            }),
+            None => {}
+        }
+    }
+
+    for row in reader.records() {
+        report.rows += 1;
+        let before = report.violations.len();
+        let row = match row {
+            Ok(row) => check_width(&row, headers.len()).map(|_| row),
+            Err(e) => Err(row_error(e)?),
+        };
+        match row {
+            Ok(row) => {
+                let line = row.position().map(|p| p.line()).unwrap_or_default();
+                for rule in rules.iter_mut() {
+                    if let Err(e) = rule.check(&row[rule.index], line) {
+                        report.violations.push(Violation {
+                            line,
+                            column: Some(rule.name.clone()),
+                            reason: e.to_string(),
+                        });
+                    }
+                }
+            }
+            // 无法解析的行 不再检查各列
+            Err(e) => report.violations.push(Violation {
+                line: e.line,
+                column: None,
+                reason: e.reason,
+            }),
+        }
+        if report.violations.len() > before {
+            report.invalid_rows += 1;
+        }
+    }
+    Ok(report)
+}
+
+fn compile_rule(column: ColumnSchema, index: usize) -> Result<ColumnRule> {
+    let context =
+        |e: anyhow::Error| anyhow::anyhow!("Invalid schema for column {}: {}", column.name, e);
+    let ty = match &column.ty {
+        Some(ty) => ty.parse::<ColumnType>().map_err(context)?,
+        None => ColumnType::String,
+    };
+    let pattern = column
+        .pattern
+        .as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| context(e.into()))?;
+    Ok(ColumnRule {
+        index,
+        ty,
+        nullable: column.nullable,
+        pattern,
+        values: column.values,
+        min: column.min,
+        max: column.max,
+        seen: column.unique.then(HashMap::new),
+        name: column.name,
+    })
+}
+
+impl ColumnRule {
+    // 检查一个单元格 每个单元格最多报告一处违规
+    fn check(&mut self, cell: &str, line: u64) -> Result<()> {
+        if is_null(cell) {
+            if !self.nullable {
+                return Err(anyhow::anyhow!("null value is not allowed"));
+            }
+            return Ok(());
+        }
+        let value = typed_value(cell, self.ty)?;
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(cell) {
+                return Err(anyhow::anyhow!("{:?} does not match /{}/", cell, pattern));
+            }
+        }
+        if let Some(values) = &self.values {
+            if !values.iter().any(|v| v == cell) {
+                return Err(anyhow::anyhow!(
+                    "{:?} is not one of {}",
+                    cell,
+                    values.join(", ")
+                ));
+            }
+        }
+        if let Some(min) = &self.min {
+            if compare(&value, cell, min).is_lt() {
+                return Err(anyhow::anyhow!("{:?} is less than min {}", cell, min));
+            }
+        }
+        if let Some(max) = &self.max {
+            if compare(&value, cell, max).is_gt() {
+                return Err(anyhow::anyhow!("{:?} is greater than max {}", cell, max));
+            }
+        }
+        if let Some(seen) = &mut self.seen {
+            if let Some(first) = seen.get(cell) {
+                return Err(anyhow::anyhow!(
+                    "duplicate value {:?}, first seen at line {}",
+                    cell,
+                    first
+                ));
+            }
+            seen.insert(cell.to_string(), line);
+        }
+        Ok(())
+    }
+}
+
+// 比较单元格与范围
+fn compare(value: &Value, cell: &str, bound: &Bound) -> std::cmp::Ordering {
+    match (bound, value) {
+        (Bound::Number(n), Value::Number(v)) => v.as_f64().unwrap_or_default().total_cmp(n),
+        (Bound::Number(n), _) => (cell.chars().count() as f64).total_cmp(n),
+        (Bound::Text(s), Value::String(v)) => v.as_str().cmp(s),
+        (Bound::Text(s), _) => cell.cmp(s),
+    }
+}
+
+impl std::fmt::Display for Bound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bound::Number(n) => write!(f, "{}", n),
+            Bound::Text(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn validate(input: &str, schema: &str) -> Result<ValidationReport> {
+        let opts = CsvValidateOpts::parse_from(["validate", "-i", input, "--schema", schema]);
+        validate_csv(&opts)
+    }
+
+    #[test]
+    fn test_validate_valid_file() -> Result<()> {
+        let report = validate("assets/juventus.csv", "fixtures/csv_schema.yaml")?;
+        assert_eq!(report.rows, 27);
+        assert!(report.is_valid(), "{:?}", report.violations);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_reports_every_violation() -> Result<()> {
+        let report = validate("fixtures/csv_invalid.csv", "fixtures/csv_schema.yaml")?;
+        let found: Vec<(u64, Option<&str>)> = report
+            .violations
+            .iter()
+            .map(|v| (v.line, v.column.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, Some("DOB")),
+                (3, Some("Kit Number")),
+                (4, Some("Position")),
+                (5, None),
+                (6, Some("Name")),
+                (6, Some("Kit Number")),
+                (7, Some("Nationality")),
+            ]
+        );
+        assert_eq!(report.rows, 6);
+        assert_eq!(report.invalid_rows, 5);
+        assert!(report.violations[5].reason.contains("first seen at line 2"));
+        assert!(report.violations[3]
+            .reason
+            .contains("expected 4 fields, found 3"));
+        Ok(())
+    }
+}
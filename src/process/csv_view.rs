@@ -0,0 +1,728 @@
+use std::{
+    collections::VecDeque,
+    io::{BufWriter, IsTerminal, Read, Write},
+};
+
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use rand::Rng;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::cli::{CsvReaderOpts, CsvViewOpts};
+
+use super::csv_reader::{open_csv, CsvReader};
+
+/// 列之间的间隔
+const GAP: &str = "  ";
+/// 完整查看时内存中最多保留的行数 窗口之外的行按需重新读取
+const WINDOW: usize = 10_000;
+
+/// 要显示的表格 表头已截断，第一列为行号
+pub struct TableView {
+    headers: Vec<String>,
+    rows: Rows,
+    widths: Vec<usize>,
+}
+
+/// 表格中的行
+enum Rows {
+    /// 按 --head、--tail 或 --sample 选出的行
+    Selected(Vec<Row>),
+    /// 完整查看时按需读取的行
+    Lazy(Box<RowWindow>),
+}
+
+/// 一行数据 保留原始字段，显示时再截断
+struct Row {
+    line: u64,
+    fields: Vec<String>,
+}
+
+impl From<csv::StringRecord> for Row {
+    fn from(record: csv::StringRecord) -> Self {
+        Self {
+            line: line_of(&record),
+            fields: record.iter().map(String::from).collect(),
+        }
+    }
+}
+
+impl Row {
+    /// 显示的单元格 第一列为行号，其余截断到列宽
+    fn cells(&self, widths: &[usize]) -> Vec<String> {
+        std::iter::once(self.line.to_string())
+            .chain(
+                self.fields
+                    .iter()
+                    .zip(widths.iter().skip(1))
+                    .map(|(f, w)| truncate(f, *w)),
+            )
+            .collect()
+    }
+
+    fn contains(&self, query: &str) -> bool {
+        contains(self.fields.iter().map(String::as_str), query)
+    }
+}
+
+/// 按需读取的行 内存中只保留一个窗口
+/// 文件向前翻到窗口之外时重新打开并跳过前面的行，标准输入只能向后读取
+struct RowWindow {
+    input: String,
+    reader: CsvReaderOpts,
+    records: csv::StringRecordsIntoIter<Box<dyn Read>>,
+    capacity: usize,
+    /// 窗口中第一行的下标
+    start: usize,
+    rows: VecDeque<Row>,
+    /// 总行数 标准输入读到结尾之前未知
+    total: Option<usize>,
+}
+
+/// 查看CSV 标准输出为终端时进入交互界面，否则输出整个表格
+pub fn process_csv_view(opts: &CsvViewOpts) -> Result<()> {
+    let mut view = load_view(opts)?;
+    let stdout = std::io::stdout();
+    if stdout.is_terminal() && !opts.no_pager {
+        return view.page(&opts.input);
+    }
+    view.write(&mut BufWriter::new(stdout.lock()))
+}
+
+/// 读取要显示的行 按 --head、--tail 或 --sample 选取，都没有时按需读取
+pub fn load_view(opts: &CsvViewOpts) -> Result<TableView> {
+    let (mut reader, headers) = open_csv(&opts.input, &opts.reader)?;
+    let headers: Vec<String> = std::iter::once("#".to_string())
+        .chain(headers.iter().map(|h| truncate(h, opts.max_width)))
+        .collect();
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width()).collect();
+    let rows = match select(&mut reader, opts)? {
+        Some(rows) => {
+            for row in &rows {
+                widen(&mut widths, row.line, &row.fields, opts.max_width);
+            }
+            Rows::Selected(rows)
+        }
+        // 标准输入不能重新读取 按开头的一个窗口计算列宽
+        None if opts.input == "-" => {
+            let mut window = RowWindow::new(&opts.input, &opts.reader, reader, WINDOW);
+            window.fill(WINDOW - 1)?;
+            for row in &window.rows {
+                widen(&mut widths, row.line, &row.fields, opts.max_width);
+            }
+            Rows::Lazy(Box::new(window))
+        }
+        // 先遍历一次统计行数和列宽 只保留当前一行
+        None => {
+            let mut record = csv::StringRecord::new();
+            let mut total = 0;
+            while reader.read_record(&mut record)? {
+                widen(&mut widths, line_of(&record), &record, opts.max_width);
+                total += 1;
+            }
+            let (reader, _) = open_csv(&opts.input, &opts.reader)?;
+            let mut window = RowWindow::new(&opts.input, &opts.reader, reader, WINDOW);
+            window.total = Some(total);
+            Rows::Lazy(Box::new(window))
+        }
+    };
+    Ok(TableView {
+        headers,
+        rows,
+        widths,
+    })
+}
+
+// 按 --head、--tail 或 --sample 选取行 都没有时返回None
+fn select(reader: &mut CsvReader, opts: &CsvViewOpts) -> Result<Option<Vec<Row>>> {
+    let mut records = reader.records();
+    let rows: Vec<csv::StringRecord> = if let Some(n) = opts.head {
+        records.take(n).collect::<Result<_, _>>()?
+    } else if let Some(n) = opts.tail {
+        let mut tail = VecDeque::with_capacity(n + 1);
+        for record in records {
+            tail.push_back(record?);
+            if tail.len() > n {
+                tail.pop_front();
+            }
+        }
+        tail.into()
+    } else if let Some(n) = opts.sample {
+        // 蓄水池抽样 只需遍历一次
+        let mut rng = rand::thread_rng();
+        let mut sample = Vec::with_capacity(n);
+        for (i, record) in records.by_ref().enumerate() {
+            let record = record?;
+            if sample.len() < n {
+                sample.push(record);
+            } else {
+                let j = rng.gen_range(0..=i);
+                if j < n {
+                    sample[j] = record;
+                }
+            }
+        }
+        sample.sort_by_key(line_of);
+        sample
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(rows.into_iter().map(Row::from).collect()))
+}
+
+fn line_of(record: &csv::StringRecord) -> u64 {
+    record.position().map(|p| p.line()).unwrap_or_default()
+}
+
+// 按截断后的单元格加宽各列 第一列为行号
+fn widen<'a>(
+    widths: &mut [usize],
+    line: u64,
+    fields: impl IntoIterator<Item = &'a (impl AsRef<str> + ?Sized + 'a)>,
+    max_width: usize,
+) {
+    widths[0] = widths[0].max(line.to_string().len());
+    for (width, field) in widths[1..].iter_mut().zip(fields) {
+        *width = (*width).max(truncate(field.as_ref(), max_width).width());
+    }
+}
+
+// 不区分大小写地查找 query已转为小写
+fn contains<'a>(fields: impl IntoIterator<Item = &'a str>, query: &str) -> bool {
+    fields.into_iter().any(|f| f.to_lowercase().contains(query))
+}
+
+/// 按显示宽度截断 超出时以…结尾，控制字符替换为空格
+pub fn truncate(cell: &str, max: usize) -> String {
+    let cell: String = cell
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if cell.width() <= max {
+        return cell;
+    }
+    let mut out = fit(&cell, max.saturating_sub(1));
+    out.push('…');
+    out
+}
+
+// 截取不超过指定显示宽度的前缀
+fn fit(text: &str, max: usize) -> String {
+    let mut width = 0;
+    text.chars()
+        .take_while(|c| {
+            width += c.width().unwrap_or(0);
+            width <= max
+        })
+        .collect()
+}
+
+// 补齐到指定的显示宽度
+fn pad(cell: &str, width: usize) -> String {
+    format!("{}{}", cell, " ".repeat(width.saturating_sub(cell.width())))
+}
+
+impl Rows {
+    /// 第i行 超出结尾时为None
+    fn get(&mut self, i: usize) -> Result<Option<&Row>> {
+        match self {
+            Rows::Selected(rows) => Ok(rows.get(i)),
+            Rows::Lazy(window) => window.get(i),
+        }
+    }
+
+    /// 总行数 未知时向后读取到第n行为止，返回已读取的行数
+    fn count(&mut self, n: usize) -> Result<usize> {
+        match self {
+            Rows::Selected(rows) => Ok(rows.len()),
+            Rows::Lazy(window) => window.count(n),
+        }
+    }
+
+    fn total(&self) -> Option<usize> {
+        match self {
+            Rows::Selected(rows) => Some(rows.len()),
+            Rows::Lazy(window) => window.total,
+        }
+    }
+
+    /// 可以显示的第一行 标准输入不能回到窗口之前
+    fn first(&self) -> usize {
+        match self {
+            Rows::Lazy(window) if window.input == "-" => window.start,
+            _ => 0,
+        }
+    }
+}
+
+impl RowWindow {
+    fn new(input: &str, reader: &CsvReaderOpts, records: CsvReader, capacity: usize) -> Self {
+        Self {
+            input: input.to_string(),
+            reader: reader.clone(),
+            records: records.into_records(),
+            capacity,
+            start: 0,
+            rows: VecDeque::with_capacity(capacity),
+            total: None,
+        }
+    }
+
+    fn get(&mut self, i: usize) -> Result<Option<&Row>> {
+        if i < self.start {
+            if self.input == "-" {
+                return Err(anyhow::anyhow!(
+                    "Only the last {} rows of stdin are kept",
+                    self.capacity
+                ));
+            }
+            // 重新读取 让第i行位于窗口中间，来回翻页时不会频繁重新读取
+            let (reader, _) = open_csv(&self.input, &self.reader)?;
+            self.records = reader.into_records();
+            self.start = 0;
+            self.rows.clear();
+            self.fill(i + self.capacity / 2)?;
+        } else {
+            self.fill(i)?;
+        }
+        Ok(self.rows.get(i - self.start))
+    }
+
+    // 向后读取到第i行或结尾 只保留最近的capacity行
+    fn fill(&mut self, i: usize) -> Result<()> {
+        while self.start + self.rows.len() <= i {
+            let Some(record) = self.records.next() else {
+                self.total = Some(self.start + self.rows.len());
+                return Ok(());
+            };
+            self.rows.push_back(Row::from(record?));
+            if self.rows.len() > self.capacity {
+                self.rows.pop_front();
+                self.start += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn count(&mut self, n: usize) -> Result<usize> {
+        if let Some(total) = self.total {
+            return Ok(total);
+        }
+        self.fill(n.saturating_sub(1))?;
+        Ok(self.total.unwrap_or(self.start + self.rows.len()))
+    }
+
+    // 文件重新读取一遍查找 不移动窗口；标准输入只能在窗口和之后的行中查找
+    fn find(&mut self, query: &str, from: usize, forward: bool) -> Result<Option<usize>> {
+        if self.input == "-" {
+            let mut i = from + 1;
+            if forward {
+                while let Some(row) = self.get(i)? {
+                    if row.contains(query) {
+                        return Ok(Some(i));
+                    }
+                    i += 1;
+                }
+            }
+            let start = self.start;
+            let matched = |i: &usize| self.rows[i - start].contains(query);
+            return Ok(match forward {
+                true => (start..=from).find(matched),
+                false => (start..from).rev().find(matched),
+            });
+        }
+        let (reader, _) = open_csv(&self.input, &self.reader)?;
+        let (mut wrapped, mut before, mut after) = (None, None, None);
+        for (i, record) in reader.into_records().enumerate() {
+            if !contains(&record?, query) {
+                continue;
+            }
+            match (forward, i.cmp(&from)) {
+                (true, std::cmp::Ordering::Greater) => return Ok(Some(i)),
+                (true, _) => {
+                    wrapped.get_or_insert(i);
+                }
+                (false, std::cmp::Ordering::Less) => before = Some(i),
+                (false, _) => after = Some(i),
+            }
+        }
+        Ok(match forward {
+            true => wrapped,
+            false => before.or(after),
+        })
+    }
+}
+
+/// 交互界面的状态
+struct Pager {
+    top: usize,
+    left: usize,
+    /// 正在输入的搜索词
+    input: Option<String>,
+    query: Option<String>,
+    matched: Option<usize>,
+    message: Option<String>,
+}
+
+/// 退出时恢复终端 出错时也会执行
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        queue!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+        stdout.flush()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut stdout = std::io::stdout();
+        let _ = queue!(stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl TableView {
+    /// 第i行显示的单元格 超出结尾时为None
+    fn cells(&mut self, i: usize) -> Result<Option<Vec<String>>> {
+        Ok(self.rows.get(i)?.map(|row| row.cells(&self.widths)))
+    }
+
+    fn separator(&self) -> Vec<String> {
+        self.widths.iter().map(|w| "-".repeat(*w)).collect()
+    }
+
+    /// 逐行输出整个表格 格式与 format_table 相同
+    pub fn write(&mut self, writer: &mut impl Write) -> Result<()> {
+        for line in [&self.headers, &self.separator()] {
+            writeln!(writer, "{}", self.render_line(line, 0, usize::MAX))?;
+        }
+        let mut i = 0;
+        while let Some(cells) = self.cells(i)? {
+            writeln!(writer, "{}", self.render_line(&cells, 0, usize::MAX))?;
+            i += 1;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// 渲染一行 行号列固定在左侧，其余列从left开始，截断到终端宽度
+    pub fn render_line(&self, cells: &[String], left: usize, width: usize) -> String {
+        let columns = std::iter::once(0).chain((left + 1)..self.widths.len());
+        let line = columns
+            .map(|i| {
+                pad(
+                    cells.get(i).map(String::as_str).unwrap_or_default(),
+                    self.widths[i],
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(GAP);
+        fit(line.trim_end(), width)
+    }
+
+    /// 查找原始字段中包含搜索词的行 不区分大小写，到结尾后从头继续
+    pub fn find(&mut self, query: &str, from: usize, forward: bool) -> Result<Option<usize>> {
+        let query = query.to_lowercase();
+        let rows = match &mut self.rows {
+            Rows::Selected(rows) => rows,
+            Rows::Lazy(window) => return window.find(&query, from, forward),
+        };
+        let n = rows.len();
+        Ok((1..=n)
+            .map(|step| match forward {
+                true => (from + step) % n,
+                false => (from + n * 2 - step) % n,
+            })
+            .find(|&i| rows[i].contains(&query)))
+    }
+
+    // 交互界面 方向键或hjkl移动，PgUp/PgDn翻页，/搜索，n/N下一个/上一个，q退出
+    fn page(&mut self, name: &str) -> Result<()> {
+        let _terminal = RawTerminal::enter()?;
+        let mut pager = Pager {
+            top: 0,
+            left: 0,
+            input: None,
+            query: None,
+            matched: None,
+            message: None,
+        };
+        loop {
+            let (width, height) = terminal::size()?;
+            let body = (height as usize).saturating_sub(3).max(1);
+            self.draw(&pager, name, width as usize, body)?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            pager.message = None;
+            if !self.handle_key(&mut pager, key, body)? {
+                return Ok(());
+            }
+        }
+    }
+
+    // 处理按键 返回false表示退出
+    fn handle_key(&mut self, pager: &mut Pager, key: KeyEvent, body: usize) -> Result<bool> {
+        let columns = self.widths.len().saturating_sub(1);
+        if let Some(input) = &mut pager.input {
+            match key.code {
+                KeyCode::Enter => {
+                    let query = std::mem::take(input);
+                    pager.input = None;
+                    if !query.is_empty() {
+                        pager.query = Some(query);
+                        pager.matched = None;
+                        self.search(pager, true, body)?;
+                    }
+                }
+                KeyCode::Esc => pager.input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return Ok(true);
+        }
+        let mut top = pager.top;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false)
+            }
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => top += 1,
+            KeyCode::Up | KeyCode::Char('k') => top = top.saturating_sub(1),
+            KeyCode::PageDown | KeyCode::Char(' ') | KeyCode::Char('f') => top += body,
+            KeyCode::PageUp | KeyCode::Char('b') => top = top.saturating_sub(body),
+            KeyCode::Home | KeyCode::Char('g') => top = 0,
+            KeyCode::End | KeyCode::Char('G') => top = usize::MAX,
+            KeyCode::Right | KeyCode::Char('l') => {
+                pager.left = (pager.left + 1).min(columns.saturating_sub(1))
+            }
+            KeyCode::Left | KeyCode::Char('h') => pager.left = pager.left.saturating_sub(1),
+            KeyCode::Char('/') => pager.input = Some(String::new()),
+            KeyCode::Char('n') => return self.search(pager, true, body).map(|_| true),
+            KeyCode::Char('N') => return self.search(pager, false, body).map(|_| true),
+            _ => {}
+        }
+        self.scroll(pager, top, body)?;
+        Ok(true)
+    }
+
+    // 滚动到top 不超过最后一页，也不回到可以显示的第一行之前
+    fn scroll(&mut self, pager: &mut Pager, top: usize, body: usize) -> Result<()> {
+        let count = self.rows.count(top.saturating_add(body))?;
+        pager.top = top.min(count.saturating_sub(body));
+        let first = self.rows.first();
+        if pager.top < first {
+            pager.top = first;
+            pager.message = Some(format!("Only the last {} rows of stdin are kept", WINDOW));
+        }
+        Ok(())
+    }
+
+    // 从当前匹配的行或第一行开始搜索 并滚动到匹配的行
+    fn search(&mut self, pager: &mut Pager, forward: bool, body: usize) -> Result<()> {
+        let Some(query) = pager.query.clone() else {
+            return Ok(());
+        };
+        let top_matches = self
+            .rows
+            .get(pager.top)?
+            .is_some_and(|row| row.contains(&query.to_lowercase()));
+        let found = match pager.matched {
+            Some(matched) => self.find(&query, matched, forward)?,
+            None if forward && top_matches => Some(pager.top),
+            None => self.find(&query, pager.top, forward)?,
+        };
+        match found {
+            Some(i) => {
+                pager.matched = Some(i);
+                if i < pager.top || i >= pager.top + body {
+                    self.scroll(pager, i, body)?;
+                }
+            }
+            None => pager.message = Some(format!("Pattern not found: {}", query)),
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, pager: &Pager, name: &str, width: usize, body: usize) -> Result<()> {
+        let mut stdout = std::io::stdout();
+        queue!(
+            stdout,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All)
+        )?;
+        let header = [
+            self.render_line(&self.headers, pager.left, width),
+            self.render_line(&self.separator(), pager.left, width),
+        ];
+        for (y, line) in header.iter().enumerate() {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, y as u16),
+                SetAttribute(Attribute::Bold),
+                Print(line),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+        let mut end = pager.top;
+        while end < pager.top + body {
+            let Some(cells) = self.cells(end)? else {
+                break;
+            };
+            queue!(stdout, cursor::MoveTo(0, (end - pager.top + 2) as u16))?;
+            if pager.matched == Some(end) {
+                queue!(stdout, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(
+                stdout,
+                Print(self.render_line(&cells, pager.left, width)),
+                SetAttribute(Attribute::Reset)
+            )?;
+            end += 1;
+        }
+
+        let status = match (&pager.input, &pager.message) {
+            (Some(input), _) => format!("/{}", input),
+            (None, Some(message)) => message.clone(),
+            (None, None) => format!(
+                "{}  rows {}-{} of {}  column {} of {}  (q quit, / search)",
+                name,
+                (pager.top + 1).min(end),
+                end,
+                self.rows
+                    .total()
+                    .map_or_else(|| "?".to_string(), |total| total.to_string()),
+                pager.left + 1,
+                self.widths.len().saturating_sub(1)
+            ),
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(0, (body + 2) as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(pad(&fit(&status, width), width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::format_table;
+    use clap::Parser;
+
+    fn view(args: &[&str]) -> Result<TableView> {
+        let opts = CsvViewOpts::parse_from(["view", "assets/juventus.csv"].iter().chain(args));
+        load_view(&opts)
+    }
+
+    fn selected(view: &TableView) -> &[Row] {
+        match &view.rows {
+            Rows::Selected(rows) => rows,
+            Rows::Lazy(_) => panic!("rows are read lazily"),
+        }
+    }
+
+    #[test]
+    fn test_truncate_by_display_width() {
+        assert_eq!(truncate("张三丰", 6), "张三丰");
+        assert_eq!(truncate("张三丰", 5), "张三…");
+        assert_eq!(truncate("张三丰", 4), "张…");
+        assert_eq!(truncate("Szczesny", 5), "Szcz…");
+        assert_eq!(truncate("a\nb", 5), "a b");
+        assert_eq!(pad("张三", 6).width(), 6);
+    }
+
+    #[test]
+    fn test_view_selects_rows() -> Result<()> {
+        let head = view(&["--head", "2"])?;
+        let rows = selected(&head);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].fields[0], "Wojciech Szczesny");
+
+        let tail = view(&["--tail", "3"])?;
+        assert_eq!(selected(&tail).len(), 3);
+        assert_eq!(selected(&tail)[2].line, 28);
+
+        let sample = view(&["--sample", "5", "--max-width", "8"])?;
+        let lines: Vec<u64> = selected(&sample).iter().map(|r| r.line).collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines.windows(2).all(|w| w[0] < w[1]));
+        assert!(sample.widths.iter().all(|w| *w <= 8));
+        // 搜索原始字段 不受截断影响
+        let mut narrow = view(&["--head", "2", "--max-width", "8"])?;
+        assert_eq!(narrow.cells(0)?.unwrap()[1], "Wojciec…");
+        assert_eq!(narrow.find("szczesny", 1, true)?, Some(0));
+
+        let mut all = view(&[])?;
+        assert!(matches!(all.rows, Rows::Lazy(_)));
+        assert_eq!(all.rows.total(), Some(27));
+        let cells = all.cells(0)?.unwrap();
+        let line = all.render_line(&cells, 1, 30);
+        assert!(line.starts_with("2   Goalkeeper"), "{}", line);
+        assert!(line.width() <= 30);
+        assert!(all.cells(27)?.is_none());
+        assert_eq!(all.find("PERIN", 0, true)?, Some(1));
+        assert_eq!(all.find("Szczesny", 0, true)?, Some(0));
+        assert_eq!(all.find("Szczesny", 0, false)?, Some(0));
+        assert_eq!(all.find("missing", 0, true)?, None);
+
+        // 按需读取的输出与一次读入全部行相同
+        let mut output = vec![];
+        all.write(&mut output)?;
+        let full = view(&["--head", "100"])?;
+        let headers: Vec<&str> = full.headers.iter().map(String::as_str).collect();
+        let rows: Vec<Vec<String>> = selected(&full)
+            .iter()
+            .map(|r| r.cells(&full.widths))
+            .collect();
+        let mut expected = format_table(&headers, &rows).join("\n");
+        expected.push('\n');
+        assert_eq!(String::from_utf8(output)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_window_keeps_bounded_rows() -> Result<()> {
+        let opts = CsvReaderOpts::parse_from(["view"]);
+        let (reader, _) = open_csv("assets/juventus.csv", &opts)?;
+        let mut window = RowWindow::new("assets/juventus.csv", &opts, reader, 5);
+        assert_eq!(window.get(20)?.map(|r| r.line), Some(22));
+        assert_eq!((window.start, window.rows.len()), (16, 5));
+        // 向前翻到窗口之外时重新读取 第3行位于窗口中间
+        assert_eq!(window.get(3)?.map(|r| r.line), Some(5));
+        assert_eq!((window.start, window.rows.len()), (1, 5));
+        assert_eq!(window.total, None);
+        assert_eq!(window.count(usize::MAX)?, 27);
+        assert!(window.get(27)?.is_none());
+        assert_eq!(window.rows.len(), 5);
+
+        assert_eq!(window.find("perin", 10, true)?, Some(1));
+        assert_eq!(window.find("perin", 10, false)?, Some(1));
+        assert_eq!(window.find("goalkeeper", 0, false)?, Some(3));
+        assert_eq!(window.find("goalkeeper", 3, true)?, Some(0));
+        assert_eq!(window.start, 22);
+        Ok(())
+    }
+}
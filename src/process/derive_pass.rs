@@ -0,0 +1,207 @@
+use anyhow::Result;
+use argon2::{Algorithm, Argon2, Params, Version};
+
+use crate::cli::Kdf;
+
+use super::{
+    gen_pass::{generate, PasswordPolicy},
+    pwned::PwnedPasswords,
+};
+
+/// 派生的密钥长度
+const KEY_LEN: usize = 32;
+/// 盐的前缀 修改派生方式时需要更换版本
+const SALT_PREFIX: &str = "rcli-derive-v1";
+/// Argon2id参数 RFC 9106推荐的第二组: 64MiB内存，3轮，4路并行
+const ARGON2_MEMORY_KIB: u32 = 64 * 1024;
+const ARGON2_ITERATIONS: u32 = 3;
+const ARGON2_LANES: u32 = 4;
+/// scrypt参数 N=2^15, r=8, p=1，约32MiB内存
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// 派生选项 相同的主密码、站点、登录名、计数器和策略总是得到相同的密码
+#[derive(Debug, Clone)]
+pub struct DeriveOptions<'a> {
+    /// 站点 不区分大小写
+    pub site: &'a str,
+    pub login: &'a str,
+    /// 需要更换密码时加一
+    pub counter: u32,
+    pub kdf: Kdf,
+}
+
+/// 由主密码派生站点密码 字符集和规则与随机生成相同
+pub fn process_derive_pass(
+    master: &str,
+    opts: &DeriveOptions,
+    policy: &PasswordPolicy,
+) -> Result<String> {
+    if master.is_empty() {
+        return Err(anyhow::anyhow!("Master password must not be empty"));
+    }
+    if opts.site.trim().is_empty() {
+        return Err(anyhow::anyhow!("Site must not be empty"));
+    }
+    let mut stream = KeyStream::new(&derive_key(master, opts)?);
+    // 泄露密码文件更新后派生结果不能改变 所以不参与重新生成，而是在派生后检查
+    let password = generate(
+        &PasswordPolicy {
+            pwned_file: None,
+            ..policy.clone()
+        },
+        |n| stream.index(n),
+    )?;
+    if let Some(path) = &policy.pwned_file {
+        if let Some(count) = PwnedPasswords::open(path)?.count(&password)? {
+            return Err(anyhow::anyhow!(
+                "Derived password was found {} times in pwned passwords, use another --counter",
+                count
+            ));
+        }
+    }
+    Ok(password)
+}
+
+// 用KDF从主密码派生密钥 盐由站点、登录名和计数器组成
+fn derive_key(master: &str, opts: &DeriveOptions) -> Result<[u8; KEY_LEN]> {
+    let salt = salt(opts);
+    let mut key = [0u8; KEY_LEN];
+    match opts.kdf {
+        Kdf::Argon2id => {
+            let params = Params::new(
+                ARGON2_MEMORY_KIB,
+                ARGON2_ITERATIONS,
+                ARGON2_LANES,
+                Some(KEY_LEN),
+            )
+            .map_err(|e| anyhow::anyhow!("Invalid argon2 params: {}", e))?;
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password_into(master.as_bytes(), &salt, &mut key)
+                .map_err(|e| anyhow::anyhow!("Argon2 failed: {}", e))?;
+        }
+        Kdf::Scrypt => {
+            let params = scrypt::Params::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P, KEY_LEN)?;
+            scrypt::scrypt(master.as_bytes(), &salt, &params, &mut key)?;
+        }
+    }
+    Ok(key)
+}
+
+// 每部分前加上长度 避免 "ab"+"c" 与 "a"+"bc" 得到相同的盐
+fn salt(opts: &DeriveOptions) -> Vec<u8> {
+    let site = opts.site.trim().to_lowercase();
+    let counter = opts.counter.to_be_bytes();
+    let mut salt = vec![];
+    for part in [
+        SALT_PREFIX.as_bytes(),
+        site.as_bytes(),
+        opts.login.as_bytes(),
+        &counter,
+    ] {
+        salt.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        salt.extend_from_slice(part);
+    }
+    salt
+}
+
+/// 由密钥扩展出的字节流 使用Blake3的XOF，可以无限读取
+struct KeyStream {
+    reader: blake3::OutputReader,
+}
+
+impl KeyStream {
+    fn new(key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            reader: blake3::Hasher::new_keyed(key).finalize_xof(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.reader.fill(&mut buf);
+        u64::from_be_bytes(buf)
+    }
+
+    /// 均匀地返回[0, n)中的一个数
+    /// 拒绝采样 丢弃不足n个的尾部，直接取模会偏向较小的数
+    fn index(&mut self, n: usize) -> usize {
+        let n = n as u64;
+        let limit = u64::MAX - u64::MAX % n;
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return (value % n) as usize;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(kdf: Kdf) -> DeriveOptions<'static> {
+        DeriveOptions {
+            site: "example.com",
+            login: "alice",
+            counter: 1,
+            kdf,
+        }
+    }
+
+    #[test]
+    fn test_derive_is_reproducible() -> Result<()> {
+        let policy = PasswordPolicy::default();
+        let password = process_derive_pass("correct horse", &options(Kdf::Argon2id), &policy)?;
+        // 固定的结果 派生方式改变时这里会失败，已有的密码也会失效
+        assert_eq!(password, "T5w3-ikGcsgWv7vX");
+        let upper = DeriveOptions {
+            site: " Example.COM ",
+            ..options(Kdf::Scrypt)
+        };
+        assert_eq!(salt(&upper), salt(&options(Kdf::Scrypt)));
+
+        let scrypt = process_derive_pass("correct horse", &options(Kdf::Scrypt), &policy)?;
+        assert_eq!(scrypt, "Jv%NmChKnh!X6^V&");
+        let next = DeriveOptions {
+            counter: 2,
+            ..options(Kdf::Scrypt)
+        };
+        assert_ne!(
+            process_derive_pass("correct horse", &next, &policy)?,
+            scrypt
+        );
+
+        let bank = PasswordPolicy::load("fixtures/password_policies.toml", "bank")?;
+        let password = process_derive_pass("correct horse", &options(Kdf::Scrypt), &bank)?;
+        assert!(bank
+            .violations(&password, &zxcvbn::zxcvbn(&password, &[])?)
+            .is_empty());
+        assert!(process_derive_pass("", &options(Kdf::Scrypt), &policy).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_stream_index_is_uniform() {
+        let mut stream = KeyStream::new(&[7u8; KEY_LEN]);
+        let mut counts = [0usize; 3];
+        for _ in 0..30000 {
+            counts[stream.index(3)] += 1;
+        }
+        assert!(counts.iter().all(|c| (9500..10500).contains(c)));
+        assert_ne!(
+            salt(&DeriveOptions {
+                site: "ab",
+                login: "c",
+                ..options(Kdf::Scrypt)
+            }),
+            salt(&DeriveOptions {
+                site: "a",
+                login: "bc",
+                ..options(Kdf::Scrypt)
+            })
+        );
+    }
+}
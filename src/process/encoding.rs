@@ -0,0 +1,106 @@
+use std::io::{Cursor, Read, Write};
+
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+use crate::{cli::CsvEncoding, get_writer};
+
+/// 自动检测编码时读取的字节数
+const SNIFF_LEN: usize = 64 * 1024;
+/// UTF-8 BOM
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 将输入转码为UTF-8 并去除BOM
+/// 有BOM时以BOM为准，否则使用指定的编码或根据开头的内容检测
+pub fn decode_reader(mut reader: Box<dyn Read>, encoding: CsvEncoding) -> Result<Box<dyn Read>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    (&mut reader)
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    let eof = head.len() < SNIFF_LEN;
+
+    let encoding = match Encoding::for_bom(&head) {
+        Some((encoding, bom_len)) => {
+            head.drain(..bom_len);
+            encoding
+        }
+        None => match encoding {
+            CsvEncoding::Known(encoding) => encoding,
+            CsvEncoding::Auto => detect_encoding(&head, eof),
+        },
+    };
+    tracing::debug!("input encoding: {}", encoding.name());
+
+    let reader = Cursor::new(head).chain(reader);
+    // UTF-8不需要转码 无效的字节由csv报告
+    if encoding == UTF_8 {
+        return Ok(Box::new(reader));
+    }
+    Ok(Box::new(
+        DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .build(reader),
+    ))
+}
+
+// 合法的UTF-8直接使用 否则由chardetng猜测，如GBK、Shift_JIS、windows-1252
+fn detect_encoding(head: &[u8], eof: bool) -> &'static Encoding {
+    match std::str::from_utf8(head) {
+        Ok(_) => UTF_8,
+        // 只是截断在多字节字符中间
+        Err(e) if e.error_len().is_none() && !eof => UTF_8,
+        Err(_) => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(head, eof);
+            detector.guess(None, true)
+        }
+    }
+}
+
+/// 获取Writer 并先写入UTF-8 BOM，Excel据此识别编码
+pub fn bom_writer(output: &str) -> Result<Box<dyn Write>> {
+    let mut writer = get_writer(output)?;
+    writer.write_all(UTF8_BOM)?;
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8], encoding: &str) -> Result<String> {
+        let reader: Box<dyn Read> = Box::new(Cursor::new(bytes.to_vec()));
+        let mut text = String::new();
+        decode_reader(reader, encoding.parse()?)?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn test_decode_detects_encoding() -> Result<()> {
+        let gbk = std::fs::read("fixtures/csv_gbk.csv")?;
+        let text = decode(&gbk, "auto")?;
+        assert!(text.starts_with("姓名,城市,得分\n张三,北京,"));
+        assert_eq!(decode(&gbk, "gb18030")?, text);
+
+        let (sjis, _, _) =
+            encoding_rs::SHIFT_JIS.encode("名前,都市\n山田太郎,東京都\n鈴木花子,大阪府\n");
+        assert_eq!(
+            decode(&sjis, "shift_jis")?,
+            "名前,都市\n山田太郎,東京都\n鈴木花子,大阪府\n"
+        );
+        assert_eq!(decode(b"caf\xe9,na\xefve\n", "latin1")?, "café,naïve\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_strips_bom() -> Result<()> {
+        assert_eq!(decode(b"\xEF\xBB\xBFa,b\n", "auto")?, "a,b\n");
+        // BOM优先于指定的编码
+        assert_eq!(decode(b"\xEF\xBB\xBFa,b\n", "gbk")?, "a,b\n");
+        assert_eq!(decode(b"\xFF\xFEa\x00,\x00b\x00", "auto")?, "a,b");
+        assert!("unknown".parse::<CsvEncoding>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use std::{io::Write, path::Path};
+
+use anyhow::Result;
+use calamine::{open_workbook_auto, Data, Range, Reader};
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+use serde_json::Value;
+
+use super::{
+    csv_infer::{parse_date, parse_naive_datetime},
+    record_writer::RecordWriter,
+};
+
+/// 支持读取的工作簿后缀
+const WORKBOOK_EXTENSIONS: &[&str] = &["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// 是否为Excel或OpenDocument工作簿 按后缀判断
+pub fn is_workbook(input: &str) -> bool {
+    Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| WORKBOOK_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// 读取工作表并转换为CSV文本 之后与CSV文件走相同的处理流程
+/// sheet为名称或从1开始的序号，默认第一个；range形如 A1:D20，只写起点时读到末尾
+pub fn read_sheet(input: &str, sheet: Option<&str>, range: Option<&str>) -> Result<Vec<u8>> {
+    let mut workbook = open_workbook_auto(input)?;
+    let names = workbook.sheet_names();
+    let name = match sheet {
+        None => names.first(),
+        Some(sheet) => names.iter().find(|n| *n == sheet).or_else(|| {
+            sheet
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| names.get(i))
+        }),
+    }
+    .ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unknown sheet: {}, available: {}",
+            sheet.unwrap_or_default(),
+            names.join(", ")
+        )
+    })?
+    .clone();
+    let data = workbook.worksheet_range(&name)?;
+    let data = match range {
+        Some(range) => select_range(&data, range)?,
+        None => data,
+    };
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in data.rows() {
+        writer.write_record(row.iter().map(cell_text))?;
+    }
+    writer.flush()?;
+    Ok(writer.into_inner().map_err(|e| e.into_error())?)
+}
+
+// 按A1形式的范围截取 位置为工作表的绝对位置
+fn select_range(data: &Range<Data>, range: &str) -> Result<Range<Data>> {
+    let (start, end) = match range.split_once(':') {
+        Some((start, end)) => (parse_cell(start)?, Some(parse_cell(end)?)),
+        None => (parse_cell(range)?, None),
+    };
+    let end = match (end, data.end()) {
+        (Some(end), _) => end,
+        (None, Some(last)) => last,
+        (None, None) => start,
+    };
+    if start.0 > end.0 || start.1 > end.1 {
+        return Err(anyhow::anyhow!("Invalid range: {}", range));
+    }
+    Ok(data.range(start, end))
+}
+
+// 解析 B3 形式的单元格 返回从0开始的(行, 列)
+fn parse_cell(cell: &str) -> Result<(u32, u32)> {
+    let invalid = || anyhow::anyhow!("Invalid cell: {}, expected A1 style", cell);
+    let cell = cell.trim().to_uppercase();
+    let split = cell
+        .find(|c: char| c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty() || !letters.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(invalid());
+    }
+    let col = letters
+        .bytes()
+        .try_fold(0u32, |acc, b| {
+            acc.checked_mul(26)?.checked_add((b - b'A' + 1) as u32)
+        })
+        .ok_or_else(invalid)?;
+    let row: u32 = digits.parse().map_err(|_| invalid())?;
+    if row == 0 {
+        return Err(invalid());
+    }
+    Ok((row - 1, col - 1))
+}
+
+// 单元格转换为文本 整数值的浮点数去掉小数，日期使用ISO-8601
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => (*f as i64).to_string(),
+        Data::Float(f) => f.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(dt) if dt.is_datetime() => {
+            let value = dt.as_f64();
+            match dt.as_datetime() {
+                Some(datetime) if value.fract() == 0.0 => datetime.date().to_string(),
+                Some(datetime) if value < 1.0 => datetime.time().to_string(),
+                Some(datetime) => datetime.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+                None => value.to_string(),
+            }
+        }
+        Data::DateTime(dt) => dt.as_f64().to_string(),
+        Data::Error(e) => e.to_string(),
+    }
+}
+
+/// Excel工作簿 第一行为加粗的列名，数字、布尔值和日期写为对应类型的单元格
+/// 工作簿需要整体生成 在finish时写出
+pub struct XlsxWriter<W: Write> {
+    writer: W,
+    worksheet: Worksheet,
+    headers: Option<Vec<String>>,
+    row: u32,
+    date: Format,
+    datetime: Format,
+}
+
+impl<W: Write> XlsxWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            worksheet: Worksheet::new(),
+            headers: None,
+            row: 0,
+            date: Format::new().set_num_format("yyyy-mm-dd"),
+            datetime: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+        }
+    }
+
+    fn write_cell(&mut self, col: u16, value: &Value) -> Result<()> {
+        let (row, sheet) = (self.row, &mut self.worksheet);
+        match value {
+            Value::Null => {}
+            Value::Bool(b) => {
+                sheet.write_boolean(row, col, *b)?;
+            }
+            Value::Number(n) => {
+                sheet.write_number(row, col, n.as_f64().unwrap_or_default())?;
+            }
+            Value::String(s) => {
+                if let Some(date) = parse_date(s) {
+                    sheet.write_datetime_with_format(row, col, date, &self.date)?;
+                } else if let Some(datetime) = parse_naive_datetime(s) {
+                    sheet.write_datetime_with_format(row, col, datetime, &self.datetime)?;
+                } else {
+                    sheet.write_string(row, col, s)?;
+                }
+            }
+            // 嵌套的值输出为Json文本
+            nested => {
+                sheet.write_string(row, col, nested.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for XlsxWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let Value::Object(map) = record else {
+            return Err(anyhow::anyhow!("xlsx output requires object records"));
+        };
+        // 第一条记录决定列名
+        let headers = match &self.headers {
+            Some(headers) => headers.clone(),
+            None => {
+                let headers: Vec<String> = map.keys().cloned().collect();
+                let bold = Format::new().set_bold();
+                for (col, header) in headers.iter().enumerate() {
+                    self.worksheet
+                        .write_string_with_format(0, col as u16, header, &bold)?;
+                }
+                self.worksheet.set_freeze_panes(1, 0)?;
+                self.row = 1;
+                self.headers = Some(headers.clone());
+                headers
+            }
+        };
+        for (col, header) in headers.iter().enumerate() {
+            if let Some(value) = map.get(header) {
+                self.write_cell(col as u16, value)?;
+            }
+        }
+        self.row += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let mut workbook = Workbook::new();
+        self.worksheet.autofit();
+        workbook.push_worksheet(std::mem::take(&mut self.worksheet));
+        self.writer.write_all(&workbook.save_to_buffer()?)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_cell() -> Result<()> {
+        assert_eq!(parse_cell("A1")?, (0, 0));
+        assert_eq!(parse_cell("b3")?, (2, 1));
+        assert_eq!(parse_cell("AA10")?, (9, 26));
+        assert!(parse_cell("A0").is_err());
+        assert!(parse_cell("12").is_err());
+        assert!(parse_cell("A").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xlsx_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("round_trip.xlsx");
+        let path = path.to_str().unwrap();
+        let mut writer = XlsxWriter::new(std::fs::File::create(path)?);
+        writer.write(&json!({"name": "Alice", "age": 30, "score": 9.5, "active": true, "joined": "2024-02-29"}))?;
+        writer.write(&json!({"name": "Bob", "age": null, "score": 7, "active": false, "joined": "2024-03-01 08:30:00"}))?;
+        writer.finish()?;
+
+        let csv = String::from_utf8(read_sheet(path, None, None)?)?;
+        assert_eq!(
+            csv,
+            "name,age,score,active,joined\n\
+             Alice,30,9.5,true,2024-02-29\n\
+             Bob,,7,false,2024-03-01T08:30:00\n"
+        );
+        let csv = String::from_utf8(read_sheet(path, Some("1"), Some("B2:C3"))?)?;
+        assert_eq!(csv, "30,9.5\n,7\n");
+        let csv = String::from_utf8(read_sheet(path, Some("Sheet1"), Some("D2"))?)?;
+        assert_eq!(csv, "true,2024-02-29\nfalse,2024-03-01T08:30:00\n");
+        assert!(read_sheet(path, Some("Missing"), None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,447 @@
+use std::io::Read;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::{cli::OutputFormat, get_writer};
+
+use super::{
+    encoding::bom_writer,
+    excel::XlsxWriter,
+    markup::{read_markdown, HtmlWriter, MarkdownWriter},
+    record_reader::{document_records, Records},
+    record_writer::{
+        CborWriter, CsvWriter, JsonWriter, MsgpackWriter, NdjsonWriter, RecordWriter, TomlWriter,
+        YamlWriter,
+    },
+    sql_writer::{SqlTable, SqlWriter, SqliteWriter},
+    xml::{read_xml, XmlWriter},
+};
+
+/// 一种结构化数据格式 加入 FORMATS 后即可用于 --format 和按后缀识别输入
+pub trait Format: Sync {
+    /// 格式名 也是 --format 的取值
+    fn name(&self) -> &'static str;
+    /// 别名 也用于按后缀识别输入文件
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// 输出文件的后缀
+    fn extension(&self) -> &'static str {
+        self.name()
+    }
+    /// 是否为表格 只有表格格式可以作为 rcli convert 的目标
+    fn is_tabular(&self) -> bool {
+        false
+    }
+    /// 创建逐条写出记录的Writer
+    fn writer(&self, output: &str, options: &FormatOptions) -> Result<Box<dyn RecordWriter>>;
+    /// 读取记录 只能输出的格式返回错误
+    fn reader(&self, _reader: Box<dyn Read>) -> Result<Records> {
+        Err(anyhow::anyhow!(
+            "{} is only supported as output format",
+            self.name()
+        ))
+    }
+}
+
+/// 各格式的输出选项 只对相应的格式生效
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// sql和sqlite的表定义
+    pub table: SqlTable,
+    /// csv写入UTF-8 BOM
+    pub bom: bool,
+    /// XML的根元素名
+    pub xml_root: String,
+    /// XML每条记录的元素名
+    pub xml_row: String,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            table: SqlTable::default(),
+            bom: false,
+            xml_root: "rows".to_string(),
+            xml_row: "row".to_string(),
+        }
+    }
+}
+
+/// 已注册的格式
+static FORMATS: &[&dyn Format] = &[
+    &JsonFormat,
+    &NdjsonFormat,
+    &YamlFormat,
+    &TomlFormat,
+    &CsvFormat,
+    &SqlFormat,
+    &SqliteFormat,
+    &XlsxFormat,
+    &MsgpackFormat,
+    &CborFormat,
+    &XmlFormat,
+    &MarkdownFormat,
+    &HtmlFormat,
+];
+
+/// 所有已注册的格式
+pub fn formats() -> impl Iterator<Item = &'static dyn Format> {
+    FORMATS.iter().copied()
+}
+
+/// 按格式名或别名查找
+pub fn find_format(name: &str) -> Option<&'static dyn Format> {
+    formats().find(|f| f.name() == name || f.aliases().contains(&name))
+}
+
+/// 代码中直接使用的格式
+impl OutputFormat {
+    pub const JSON: Self = Self(&JsonFormat);
+    pub const NDJSON: Self = Self(&NdjsonFormat);
+    pub const YAML: Self = Self(&YamlFormat);
+    pub const TOML: Self = Self(&TomlFormat);
+    pub const CSV: Self = Self(&CsvFormat);
+    pub const SQL: Self = Self(&SqlFormat);
+    pub const SQLITE: Self = Self(&SqliteFormat);
+    pub const XLSX: Self = Self(&XlsxFormat);
+}
+
+struct JsonFormat;
+
+impl Format for JsonFormat {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(JsonWriter::new(get_writer(output)?)))
+    }
+
+    fn reader(&self, reader: Box<dyn Read>) -> Result<Records> {
+        document(serde_json::from_reader(reader)?)
+    }
+}
+
+/// 每行一个Json对象 完全流式输出
+struct NdjsonFormat;
+
+impl Format for NdjsonFormat {
+    fn name(&self) -> &'static str {
+        "ndjson"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["jsonl"]
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(NdjsonWriter::new(get_writer(output)?)))
+    }
+
+    // 逐行读取
+    fn reader(&self, reader: Box<dyn Read>) -> Result<Records> {
+        let records = serde_json::Deserializer::from_reader(reader)
+            .into_iter::<Value>()
+            .map(|v| v.map_err(anyhow::Error::from));
+        Ok(Box::new(records))
+    }
+}
+
+struct YamlFormat;
+
+impl Format for YamlFormat {
+    fn name(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["yml"]
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(YamlWriter::new(get_writer(output)?)))
+    }
+
+    fn reader(&self, reader: Box<dyn Read>) -> Result<Records> {
+        document(serde_yaml::from_reader(reader)?)
+    }
+}
+
+struct TomlFormat;
+
+impl Format for TomlFormat {
+    fn name(&self) -> &'static str {
+        "toml"
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(TomlWriter::new(get_writer(output)?)))
+    }
+
+    fn reader(&self, mut reader: Box<dyn Read>) -> Result<Records> {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf)?;
+        document(toml::from_str(&buf)?)
+    }
+}
+
+/// CSV 嵌套的值输出为Json文本
+struct CsvFormat;
+
+impl Format for CsvFormat {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn is_tabular(&self) -> bool {
+        true
+    }
+
+    fn writer(&self, output: &str, options: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        let writer = match options.bom {
+            true => bom_writer(output)?,
+            false => get_writer(output)?,
+        };
+        Ok(Box::new(CsvWriter::new(writer)))
+    }
+}
+
+/// CREATE TABLE 和批量 INSERT 语句
+struct SqlFormat;
+
+impl Format for SqlFormat {
+    fn name(&self) -> &'static str {
+        "sql"
+    }
+
+    fn writer(&self, output: &str, options: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(SqlWriter::new(
+            get_writer(output)?,
+            options.table.clone(),
+        )))
+    }
+}
+
+/// SQLite数据库文件 直接打开输出路径
+struct SqliteFormat;
+
+impl Format for SqliteFormat {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["db"]
+    }
+
+    fn extension(&self) -> &'static str {
+        "db"
+    }
+
+    fn writer(&self, output: &str, options: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(SqliteWriter::new(output, options.table.clone())?))
+    }
+}
+
+/// Excel工作簿 列名加粗，数字、布尔值和日期为对应类型的单元格
+struct XlsxFormat;
+
+impl Format for XlsxFormat {
+    fn name(&self) -> &'static str {
+        "xlsx"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["excel"]
+    }
+
+    fn is_tabular(&self) -> bool {
+        true
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(XlsxWriter::new(get_writer(output)?)))
+    }
+}
+
+/// MessagePack 记录数组
+struct MsgpackFormat;
+
+impl Format for MsgpackFormat {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["mpk", "messagepack"]
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(MsgpackWriter::new(get_writer(output)?)?))
+    }
+
+    fn reader(&self, reader: Box<dyn Read>) -> Result<Records> {
+        document(rmp_serde::from_read(reader)?)
+    }
+}
+
+/// CBOR 记录数组
+struct CborFormat;
+
+impl Format for CborFormat {
+    fn name(&self) -> &'static str {
+        "cbor"
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(CborWriter::new(get_writer(output)?)))
+    }
+
+    fn reader(&self, reader: Box<dyn Read>) -> Result<Records> {
+        document(ciborium::from_reader(reader)?)
+    }
+}
+
+/// XML 每条记录一个元素，字段为子元素
+struct XmlFormat;
+
+impl Format for XmlFormat {
+    fn name(&self) -> &'static str {
+        "xml"
+    }
+
+    fn writer(&self, output: &str, options: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(XmlWriter::new(
+            get_writer(output)?,
+            &options.xml_root,
+            &options.xml_row,
+        )))
+    }
+
+    fn reader(&self, reader: Box<dyn Read>) -> Result<Records> {
+        document(Value::Array(read_xml(reader)?))
+    }
+}
+
+/// Markdown表格
+struct MarkdownFormat;
+
+impl Format for MarkdownFormat {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["md"]
+    }
+
+    fn extension(&self) -> &'static str {
+        "md"
+    }
+
+    fn is_tabular(&self) -> bool {
+        true
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(MarkdownWriter::new(get_writer(output)?)))
+    }
+
+    fn reader(&self, reader: Box<dyn Read>) -> Result<Records> {
+        document(Value::Array(read_markdown(reader)?))
+    }
+}
+
+/// HTML表格 只支持输出
+struct HtmlFormat;
+
+impl Format for HtmlFormat {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["htm"]
+    }
+
+    fn is_tabular(&self) -> bool {
+        true
+    }
+
+    fn writer(&self, output: &str, _: &FormatOptions) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(HtmlWriter::new(get_writer(output)?)))
+    }
+}
+
+// 整体解析的文档 取出其中的记录
+fn document(document: Value) -> Result<Records> {
+    Ok(Box::new(document_records(document).into_iter().map(Ok)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_find_format() {
+        assert_eq!(find_format("yml").map(|f| f.name()), Some("yaml"));
+        assert_eq!(find_format("md").map(|f| f.extension()), Some("md"));
+        assert!(find_format("parquet").is_none());
+        assert_eq!(
+            "JSONL".parse::<OutputFormat>().ok(),
+            Some(OutputFormat::NDJSON)
+        );
+        let err = "parquet".parse::<OutputFormat>().unwrap_err().to_string();
+        assert!(
+            err.contains("msgpack, cbor, xml, markdown, html"),
+            "{}",
+            err
+        );
+        // 名称和别名不能重复
+        let mut names: Vec<&str> = formats()
+            .flat_map(|f| std::iter::once(f.name()).chain(f.aliases().iter().copied()))
+            .collect();
+        let total = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), total);
+    }
+
+    #[test]
+    fn test_round_trip_formats() -> Result<()> {
+        let records = vec![
+            json!({"Name": "Alice", "Age": 30, "Score": 9.5, "Tags": ["a", "b"]}),
+            json!({"Name": "Bob <&>", "Age": null, "Score": -1, "Tags": []}),
+        ];
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("records");
+        let path = path.to_str().unwrap();
+        for name in ["json", "ndjson", "yaml", "msgpack", "cbor"] {
+            let format = find_format(name).unwrap();
+            let mut writer = format.writer(path, &FormatOptions::default())?;
+            for record in &records {
+                writer.write(record)?;
+            }
+            writer.finish()?;
+            let read: Vec<Value> = format
+                .reader(Box::new(std::fs::File::open(path)?))?
+                .collect::<Result<_>>()?;
+            assert_eq!(read, records, "{}", name);
+        }
+        // 没有记录时也是合法的空数组
+        for name in ["msgpack", "cbor"] {
+            let format = find_format(name).unwrap();
+            format.writer(path, &FormatOptions::default())?.finish()?;
+            let read = format.reader(Box::new(std::fs::File::open(path)?))?;
+            assert_eq!(read.count(), 0, "{}", name);
+        }
+        assert!(find_format("html")
+            .unwrap()
+            .reader(Box::new(std::io::empty()))
+            .is_err());
+        Ok(())
+    }
+}
@@ -1,50 +1,418 @@
+use std::collections::{HashMap, HashSet};
+
 use anyhow::Result;
 use rand::prelude::*;
+use serde::Deserialize;
+use zxcvbn::{zxcvbn, Entropy};
+
+use super::pwned::PwnedPasswords;
+
+const NUMBERS: &str = "0123456789";
+const SPECIALS: &str = "!@#$%^&*_-";
+const UPPERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWERS: &str = "abcdefghijklmnopqrstuvwxyz";
+/// 容易混淆的字符
+const AMBIGUOUS: &str = "0Oo1Il|";
+/// 未达到强度要求时最多重新生成的次数
+pub const MAX_ATTEMPTS: usize = 1000;
+
+/// 密码策略 可以从Toml文件中按名称加载
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    /// 密码长度 检查密码时为最小长度
+    pub length: usize,
+    pub no_number: bool,
+    pub no_special: bool,
+    pub no_upper: bool,
+    pub no_lower: bool,
+    /// 每类字符的最少个数 不使用的类别忽略
+    pub min_number: usize,
+    pub min_special: usize,
+    pub min_upper: usize,
+    pub min_lower: usize,
+    /// 允许的字符 指定时只使用其中的字符，非字母数字的字符作为特殊字符
+    pub allowed: Option<String>,
+    /// 禁止的字符
+    pub forbidden: String,
+    /// 排除容易混淆的字符 如 0O1lI
+    pub exclude_ambiguous: bool,
+    /// 同一字符最多连续出现的次数
+    pub max_repeat: Option<usize>,
+    /// 最低的zxcvbn评分 0-4
+    pub min_score: Option<u8>,
+    /// zxcvbn估算的最低熵 单位为bit
+    pub min_entropy: Option<f64>,
+    /// 本地的HIBP泄露密码哈希文件或按前缀下载的目录 出现在其中的密码不合格
+    pub pwned_file: Option<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            length: 16,
+            no_number: false,
+            no_special: false,
+            no_upper: false,
+            no_lower: false,
+            min_number: 1,
+            min_special: 1,
+            min_upper: 1,
+            min_lower: 1,
+            allowed: None,
+            forbidden: String::new(),
+            exclude_ambiguous: true,
+            max_repeat: None,
+            min_score: None,
+            min_entropy: None,
+            pwned_file: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// 从Toml文件中加载策略 每个策略是一个表，如 [github]
+    pub fn load(path: &str, name: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut policies: HashMap<String, PasswordPolicy> = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid policy file {}: {}", path, e))?;
+        policies.remove(name).ok_or_else(|| {
+            let mut names: Vec<&String> = policies.keys().collect();
+            names.sort();
+            anyhow::anyhow!(
+                "Unknown policy: {}, available: {}",
+                name,
+                names
+                    .iter()
+                    .map(|n| n.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+    }
 
-const NUMBERS: &[u8] = b"123456789";
-const SPECIALS: &[u8] = b"!@#$%^&*_-";
-const UPPERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
-const LOWERS: &[u8] = b"abcdefghijkmnpqrstuvwxyz";
-
-pub fn process_gen_pass(
-    length: u8,
-    no_number: bool,
-    no_special: bool,
-    no_upper: bool,
-    no_lower: bool,
-) -> Result<String> {
+    // 按策略得到各类字符集及其最少个数
+    fn classes(&self) -> Result<Vec<(Vec<char>, usize)>> {
+        let classes = [
+            (NUMBERS, self.no_number, self.min_number),
+            (SPECIALS, self.no_special, self.min_special),
+            (UPPERS, self.no_upper, self.min_upper),
+            (LOWERS, self.no_lower, self.min_lower),
+        ];
+        let mut result = vec![];
+        for (i, (base, disabled, min)) in classes.into_iter().enumerate() {
+            if disabled {
+                continue;
+            }
+            let chars: Vec<char> = match &self.allowed {
+                // 允许的字符中非字母数字的都是特殊字符
+                Some(allowed) if i == 1 => allowed
+                    .chars()
+                    .filter(|c| !c.is_ascii_alphanumeric())
+                    .collect(),
+                Some(allowed) => base.chars().filter(|c| allowed.contains(*c)).collect(),
+                None => base.chars().collect(),
+            };
+            let mut seen = HashSet::new();
+            let chars: Vec<char> = chars
+                .into_iter()
+                .filter(|c| !self.forbidden.contains(*c))
+                .filter(|c| !(self.exclude_ambiguous && AMBIGUOUS.contains(*c)))
+                .filter(|c| seen.insert(*c))
+                .collect();
+            if chars.is_empty() {
+                if min > 0 {
+                    return Err(anyhow::anyhow!(
+                        "Policy requires {} {} characters but none are allowed",
+                        min,
+                        ["number", "special", "upper", "lower"][i]
+                    ));
+                }
+                continue;
+            }
+            result.push((chars, min));
+        }
+        Ok(result)
+    }
+
+    /// 检查已有的密码违反了哪些规则 非字母数字的字符都算作特殊字符
+    pub fn violations(&self, password: &str, entropy: &Entropy) -> Vec<String> {
+        let mut violations = vec![];
+        let length = password.chars().count();
+        if length < self.length {
+            violations.push(format!("length {} is shorter than {}", length, self.length));
+        }
+        let count = |is_class: fn(&char) -> bool| password.chars().filter(is_class).count();
+        let classes = [
+            (
+                "number",
+                count(char::is_ascii_digit),
+                self.no_number,
+                self.min_number,
+            ),
+            (
+                "special",
+                count(|c| !c.is_ascii_alphanumeric()),
+                self.no_special,
+                self.min_special,
+            ),
+            (
+                "upper",
+                count(char::is_ascii_uppercase),
+                self.no_upper,
+                self.min_upper,
+            ),
+            (
+                "lower",
+                count(char::is_ascii_lowercase),
+                self.no_lower,
+                self.min_lower,
+            ),
+        ];
+        for (name, count, disabled, min) in classes {
+            if disabled && count > 0 {
+                violations.push(format!("contains {} characters", name));
+            } else if !disabled && count < min {
+                violations.push(format!(
+                    "has {} {} characters, requires at least {}",
+                    count, name, min
+                ));
+            }
+        }
+        let rules = [
+            (
+                "not allowed",
+                password.chars().any(|c| {
+                    self.allowed
+                        .as_ref()
+                        .is_some_and(|allowed| !allowed.contains(c))
+                }),
+            ),
+            (
+                "forbidden",
+                password.chars().any(|c| self.forbidden.contains(c)),
+            ),
+            (
+                "ambiguous",
+                self.exclude_ambiguous && password.chars().any(|c| AMBIGUOUS.contains(c)),
+            ),
+        ];
+        for (rule, violated) in rules {
+            if violated {
+                violations.push(format!("contains {} characters", rule));
+            }
+        }
+        if let Some(max) = self.max_repeat {
+            let run = longest_run(password);
+            if run > max {
+                violations.push(format!(
+                    "repeats a character {} times in a row, max {}",
+                    run, max
+                ));
+            }
+        }
+        if let Some(min) = self.min_score {
+            if entropy.score() < min {
+                violations.push(format!("score {} is below {}", entropy.score(), min));
+            }
+        }
+        if let Some(min) = self.min_entropy {
+            let bits = entropy_bits(entropy);
+            if bits < min {
+                violations.push(format!("entropy {:.2} bits is below {}", bits, min));
+            }
+        }
+        violations
+    }
+}
+
+/// 按策略生成密码 未达到强度要求时重新生成
+pub fn process_gen_pass(policy: &PasswordPolicy) -> Result<String> {
     let mut rng = rand::thread_rng();
-    let mut password = Vec::new();
-    let mut chars = vec![];
-    if !no_number {
-        chars.extend_from_slice(NUMBERS);
+    generate(policy, |n| rng.gen_range(0..n))
+}
 
-        password.push(*NUMBERS.choose(&mut rng).expect("字符集不可为空"));
+/// 按策略生成密码 index(n)需要均匀地返回[0, n)中的一个数，
+/// 随机生成和由主密码派生共用同一套字符集和规则
+pub fn generate(policy: &PasswordPolicy, mut index: impl FnMut(usize) -> usize) -> Result<String> {
+    let classes = policy.classes()?;
+    if policy.length == 0 {
+        return Err(anyhow::anyhow!("Password length must be greater than 0"));
     }
-    if !no_special {
-        chars.extend_from_slice(SPECIALS);
-        password.push(*SPECIALS.choose(&mut rng).expect("字符集不可为空"));
+    if classes.is_empty() {
+        return Err(anyhow::anyhow!("Policy allows no characters"));
     }
-    if !no_upper {
-        chars.extend_from_slice(UPPERS);
-        password.push(*UPPERS.choose(&mut rng).expect("字符集不可为空"));
+    let required: usize = classes.iter().map(|(_, min)| min).sum();
+    if required > policy.length {
+        return Err(anyhow::anyhow!(
+            "Password length {} is shorter than the {} required characters",
+            policy.length,
+            required
+        ));
     }
-    if !no_lower {
-        chars.extend_from_slice(LOWERS);
-        password.push(*LOWERS.choose(&mut rng).expect("字符集不可为空"));
+    if policy.min_score.is_some_and(|score| score > 4) {
+        return Err(anyhow::anyhow!("min_score must be between 0 and 4"));
     }
 
-    // 从第四位往后随机
-    for _ in 4..length {
-        let idx = rng.gen_range(0..chars.len());
-        password.push(chars[idx]);
+    let pwned = policy
+        .pwned_file
+        .as_deref()
+        .map(PwnedPasswords::open)
+        .transpose()?;
+
+    let chars: Vec<char> = classes.iter().flat_map(|(c, _)| c).copied().collect();
+    for _ in 0..MAX_ATTEMPTS {
+        let mut password = Vec::with_capacity(policy.length);
+        // 先满足每类字符的最少个数
+        for (class, min) in &classes {
+            for _ in 0..*min {
+                password.push(class[index(class.len())]);
+            }
+        }
+        while password.len() < policy.length {
+            password.push(chars[index(chars.len())]);
+        }
+
+        // Fisher-Yates乱序
+        for i in (1..password.len()).rev() {
+            password.swap(i, index(i + 1));
+        }
+        let password: String = password.into_iter().collect();
+        if meets_policy(&password, policy, pwned.as_ref())? {
+            return Ok(password);
+        }
     }
+    Err(anyhow::anyhow!(
+        "Could not generate a password meeting the policy after {} attempts, try a longer length",
+        MAX_ATTEMPTS
+    ))
+}
 
-    // 对密码进行乱序
-    password.shuffle(&mut rng);
+// 检查连续重复、是否已泄露和强度
+fn meets_policy(
+    password: &str,
+    policy: &PasswordPolicy,
+    pwned: Option<&PwnedPasswords>,
+) -> Result<bool> {
+    if let Some(max) = policy.max_repeat {
+        if longest_run(password) > max {
+            return Ok(false);
+        }
+    }
+    if let Some(pwned) = pwned {
+        if pwned.count(password)?.is_some() {
+            return Ok(false);
+        }
+    }
+    if policy.min_score.is_none() && policy.min_entropy.is_none() {
+        return Ok(true);
+    }
+    let entropy = zxcvbn(password, &[])?;
+    Ok(policy.min_score.is_none_or(|s| entropy.score() >= s)
+        && policy
+            .min_entropy
+            .is_none_or(|e| entropy_bits(&entropy) >= e))
+}
+
+/// zxcvbn估算的熵 单位为bit
+pub fn entropy_bits(entropy: &Entropy) -> f64 {
+    entropy.guesses_log10() * std::f64::consts::LOG2_10
+}
+
+// 同一字符最长的连续长度
+fn longest_run(password: &str) -> usize {
+    let chars: Vec<char> = password.chars().collect();
+    chars
+        .chunk_by(|a, b| a == b)
+        .map(|run| run.len())
+        .max()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // 转换为字符串
-    let password = String::from_utf8(password)?;
+    fn count(password: &str, set: &str) -> usize {
+        password.chars().filter(|c| set.contains(*c)).count()
+    }
+
+    #[test]
+    fn test_short_length_and_minimums() -> Result<()> {
+        for length in 1..4 {
+            let policy = PasswordPolicy {
+                length,
+                min_number: 0,
+                min_special: 0,
+                min_upper: 0,
+                min_lower: 1,
+                ..Default::default()
+            };
+            assert_eq!(process_gen_pass(&policy)?.chars().count(), length);
+        }
+        assert!(process_gen_pass(&PasswordPolicy {
+            length: 3,
+            ..Default::default()
+        })
+        .is_err());
+
+        let policy = PasswordPolicy {
+            length: 12,
+            min_number: 4,
+            min_special: 3,
+            no_upper: true,
+            forbidden: "abc".to_string(),
+            max_repeat: Some(1),
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            let password = process_gen_pass(&policy)?;
+            assert_eq!(password.len(), 12);
+            assert!(count(&password, NUMBERS) >= 4);
+            assert!(count(&password, SPECIALS) >= 3);
+            assert_eq!(count(&password, UPPERS), 0);
+            assert_eq!(count(&password, "abc"), 0);
+            assert_eq!(count(&password, AMBIGUOUS), 0);
+            assert_eq!(longest_run(&password), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_policy() -> Result<()> {
+        let policy = PasswordPolicy::load("fixtures/password_policies.toml", "bank")?;
+        assert_eq!(policy.length, 8);
+        let password = process_gen_pass(&policy)?;
+        assert!(password.chars().all(|c| "23456789ABCDEF!#".contains(c)));
+        assert!(count(&password, "!#") >= 2);
 
-    Ok(password)
+        let policy = PasswordPolicy::load("fixtures/password_policies.toml", "strong")?;
+        let password = process_gen_pass(&policy)?;
+        assert!(zxcvbn(&password, &[])?.score() >= 4);
+
+        let err = PasswordPolicy::load("fixtures/password_policies.toml", "missing").unwrap_err();
+        assert!(err.to_string().contains("available: bank, strong"));
+        assert_eq!(longest_run("aabbbc"), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_pwned() -> Result<()> {
+        // 只能生成 "a"，而它在泄露密码中
+        let policy = PasswordPolicy {
+            length: 1,
+            allowed: Some("a".to_string()),
+            min_number: 0,
+            min_special: 0,
+            min_upper: 0,
+            ..Default::default()
+        };
+        assert_eq!(process_gen_pass(&policy)?, "a");
+        let policy = PasswordPolicy {
+            pwned_file: Some("fixtures/pwned/pwned-sha1.txt".to_string()),
+            ..policy
+        };
+        assert!(process_gen_pass(&policy).is_err());
+        Ok(())
+    }
 }
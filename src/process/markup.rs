@@ -0,0 +1,259 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use anyhow::Result;
+use quick_xml::escape::escape;
+use serde_json::{Map, Value};
+
+use super::record_writer::{cell_text, RecordWriter};
+
+/// Markdown表格 列名取自第一条记录
+pub struct MarkdownWriter<W: Write> {
+    writer: W,
+    headers: Option<Vec<String>>,
+}
+
+impl<W: Write> MarkdownWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            headers: None,
+        }
+    }
+}
+
+impl<W: Write> RecordWriter for MarkdownWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let map = table_row(record, "markdown")?;
+        let headers = match &self.headers {
+            Some(headers) => headers,
+            None => {
+                let headers: Vec<String> = map.keys().cloned().collect();
+                write_markdown_row(&mut self.writer, headers.iter().cloned())?;
+                write_markdown_row(&mut self.writer, headers.iter().map(|_| "---".to_string()))?;
+                self.headers.insert(headers)
+            }
+        };
+        write_markdown_row(
+            &mut self.writer,
+            headers.iter().map(|h| cell_text(map.get(h))),
+        )
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// 写入一行 | 需要转义，换行替换为<br>
+fn write_markdown_row(writer: &mut impl Write, cells: impl Iterator<Item = String>) -> Result<()> {
+    let cells: Vec<String> = cells
+        .map(|c| {
+            c.replace('|', "\\|")
+                .replace("\r\n", "<br>")
+                .replace('\n', "<br>")
+        })
+        .collect();
+    writeln!(writer, "| {} |", cells.join(" | "))?;
+    Ok(())
+}
+
+/// 读取文档中的第一个Markdown表格 单元格均为字符串，空单元格为null
+pub fn read_markdown(reader: impl Read) -> Result<Vec<Value>> {
+    let mut lines = BufReader::new(reader)
+        .lines()
+        .map(|line| line.map(|l| l.trim().to_string()))
+        .skip_while(|line| line.as_ref().is_ok_and(|l| !l.starts_with('|')));
+    let Some(header) = lines.next().transpose()? else {
+        return Ok(vec![]);
+    };
+    let headers = split_markdown_row(&header);
+    let separator = lines.next().transpose()?.unwrap_or_default();
+    if !split_markdown_row(&separator)
+        .iter()
+        .all(|c| !c.is_empty() && c.chars().all(|c| matches!(c, '-' | ':')))
+    {
+        return Err(anyhow::anyhow!(
+            "Invalid markdown table separator: {}",
+            separator
+        ));
+    }
+
+    let mut records = vec![];
+    for line in lines {
+        let line = line?;
+        // 表格在第一个非表格行处结束
+        if !line.starts_with('|') {
+            break;
+        }
+        let cells = split_markdown_row(&line);
+        let record: Map<String, Value> = headers
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                let value = match cells.get(i) {
+                    Some(cell) if !cell.is_empty() => Value::String(cell.replace("<br>", "\n")),
+                    _ => Value::Null,
+                };
+                (h.clone(), value)
+            })
+            .collect();
+        records.push(Value::Object(record));
+    }
+    Ok(records)
+}
+
+// 按未转义的 | 拆分单元格
+fn split_markdown_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('|') => cell.push('|'),
+                Some(next) => {
+                    cell.push('\\');
+                    cell.push(next);
+                }
+                None => cell.push('\\'),
+            },
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+    // 没有结尾的 | 时最后一格也有效
+    if !cell.trim().is_empty() {
+        cells.push(cell.trim().to_string());
+    }
+    cells
+}
+
+/// HTML表格 列名取自第一条记录
+pub struct HtmlWriter<W: Write> {
+    writer: W,
+    headers: Option<Vec<String>>,
+}
+
+impl<W: Write> HtmlWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            headers: None,
+        }
+    }
+}
+
+impl<W: Write> RecordWriter for HtmlWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let map = table_row(record, "html")?;
+        let headers = match &self.headers {
+            Some(headers) => headers,
+            None => {
+                let headers: Vec<String> = map.keys().cloned().collect();
+                writeln!(self.writer, "<table>\n  <thead>")?;
+                write_html_row(&mut self.writer, "th", headers.iter().cloned())?;
+                writeln!(self.writer, "  </thead>\n  <tbody>")?;
+                self.headers.insert(headers)
+            }
+        };
+        write_html_row(
+            &mut self.writer,
+            "td",
+            headers.iter().map(|h| cell_text(map.get(h))),
+        )
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self.headers {
+            Some(_) => writeln!(self.writer, "  </tbody>\n</table>")?,
+            None => writeln!(self.writer, "<table></table>")?,
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn write_html_row(
+    writer: &mut impl Write,
+    tag: &str,
+    cells: impl Iterator<Item = String>,
+) -> Result<()> {
+    write!(writer, "    <tr>")?;
+    for cell in cells {
+        write!(writer, "<{tag}>{}</{tag}>", escape(&cell))?;
+    }
+    writeln!(writer, "</tr>")?;
+    Ok(())
+}
+
+// 表格的每条记录必须是对象
+fn table_row<'a>(record: &'a Value, format: &str) -> Result<&'a Map<String, Value>> {
+    match record {
+        Value::Object(map) => Ok(map),
+        _ => Err(anyhow::anyhow!(
+            "{} output requires objects, got {}",
+            format,
+            record
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({"Name": "Alice | A", "Age": 30, "Note": "line1\nline2"}),
+            json!({"Name": "<Bob>", "Age": null, "Note": "a & b"}),
+        ]
+    }
+
+    fn write_all(writer: &mut dyn RecordWriter) -> Result<()> {
+        for record in records() {
+            writer.write(&record)?;
+        }
+        writer.finish()
+    }
+
+    #[test]
+    fn test_markdown_round_trip() -> Result<()> {
+        let mut buf = Vec::new();
+        write_all(&mut MarkdownWriter::new(&mut buf))?;
+        let text = String::from_utf8(buf)?;
+        assert_eq!(
+            text,
+            "| Name | Age | Note |\n\
+             | --- | --- | --- |\n\
+             | Alice \\| A | 30 | line1<br>line2 |\n\
+             | <Bob> |  | a & b |\n"
+        );
+        let read = read_markdown(format!("# Players\n\n{}\nmore text\n", text).as_bytes())?;
+        assert_eq!(
+            read,
+            vec![
+                json!({"Name": "Alice | A", "Age": "30", "Note": "line1\nline2"}),
+                json!({"Name": "<Bob>", "Age": null, "Note": "a & b"}),
+            ]
+        );
+        assert!(read_markdown("| a |\n| b |\n".as_bytes()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_html_writer_escapes() -> Result<()> {
+        let mut buf = Vec::new();
+        write_all(&mut HtmlWriter::new(&mut buf))?;
+        let text = String::from_utf8(buf)?;
+        assert!(text.starts_with(
+            "<table>\n  <thead>\n    <tr><th>Name</th><th>Age</th><th>Note</th></tr>\n"
+        ));
+        assert!(text.contains("<tr><td>&lt;Bob&gt;</td><td></td><td>a &amp; b</td></tr>"));
+        assert!(text.ends_with("  </tbody>\n</table>\n"));
+        Ok(())
+    }
+}
@@ -1,15 +1,71 @@
 mod b64;
+mod convert;
+mod csv_agg;
+mod csv_cat;
 mod csv_convert;
+mod csv_diff;
+mod csv_filter;
+mod csv_infer;
+mod csv_join;
+mod csv_protect;
+mod csv_query;
+mod csv_reader;
+mod csv_split;
+mod csv_stats;
+mod csv_template;
+mod csv_validate;
+mod csv_view;
+mod derive_pass;
+mod encoding;
+mod excel;
+mod format;
 mod gen_pass;
 mod http_serve;
 mod jwt;
+mod markup;
+mod pass_check;
+mod passphrase;
+mod projection;
+mod pwned;
+mod record_reader;
+mod record_writer;
+mod row_writer;
+mod sql_writer;
 mod text;
+mod unflatten;
+mod xml;
 
 pub use b64::{process_decode, process_encode};
+pub use convert::process_convert;
+pub use csv_agg::{aggregate_csv, process_csv_agg, AggTable};
+pub use csv_cat::process_csv_cat;
 pub use csv_convert::process_csv;
-pub use gen_pass::process_gen_pass;
+pub use csv_diff::{diff_csv, process_csv_diff, DiffSummary};
+pub use csv_join::process_csv_join;
+pub use csv_query::{process_csv_query, query_csv, QueryResult};
+pub use csv_reader::{column_index, open_csv, CsvReader, RowError};
+pub use csv_split::process_csv_split;
+pub use csv_stats::{
+    format_table, process_csv_stats, write_csv_stats, write_table, ColumnStats, StatsCollector,
+};
+pub use csv_template::TemplateWriter;
+pub use csv_validate::{process_csv_validate, validate_csv, ValidationReport, Violation};
+pub use csv_view::process_csv_view;
+pub use derive_pass::{process_derive_pass, DeriveOptions};
+pub use encoding::{bom_writer, decode_reader};
+pub use excel::{is_workbook, read_sheet, XlsxWriter};
+pub use format::{find_format, formats, Format, FormatOptions};
+pub use gen_pass::{entropy_bits, process_gen_pass, PasswordPolicy};
 pub use http_serve::process_http_serve;
 pub use jwt::{process_jwt_sign, process_jwt_verify};
+pub use pass_check::{
+    check_passwords, process_pass_check, CheckOptions, CheckReport, PasswordCheck,
+};
+pub use passphrase::{process_gen_passphrase, Passphrase, PassphraseOptions};
+pub use pwned::PwnedPasswords;
+pub use record_reader::{read_records, Records};
+pub use record_writer::{record_writer, RecordWriter};
+pub use sql_writer::SqlTable;
 pub use text::{
     process_text_decrypt, process_text_encrypt, process_text_generate_key, process_text_sign,
     process_text_verify,
@@ -0,0 +1,274 @@
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+
+use anyhow::Result;
+use serde::Serialize;
+use zxcvbn::zxcvbn;
+
+use crate::{cli::ReportFormat, get_reader, get_writer};
+
+use super::{
+    gen_pass::{entropy_bits, PasswordPolicy},
+    pwned::PwnedPasswords,
+};
+
+/// 单个密码的检查结果 不包含密码本身，只用行号标识
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordCheck {
+    pub line: usize,
+    /// zxcvbn评分 0-4
+    pub score: u8,
+    /// zxcvbn估算的熵 单位为bit
+    pub entropy: f64,
+    pub crack_times: CrackTimes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+    /// 在泄露密码中出现的次数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pwned: Option<u64>,
+    /// 违反的策略规则
+    pub violations: Vec<String>,
+}
+
+impl PasswordCheck {
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// 不同攻击场景下估算的破解时间
+#[derive(Debug, Clone, Serialize)]
+pub struct CrackTimes {
+    /// 在线攻击 限速每小时100次
+    pub online_throttled: String,
+    /// 在线攻击 不限速每秒10次
+    pub online_unthrottled: String,
+    /// 离线攻击 慢哈希每秒1万次
+    pub offline_slow_hash: String,
+    /// 离线攻击 快哈希每秒100亿次
+    pub offline_fast_hash: String,
+}
+
+/// 检查报告
+#[derive(Debug, Default, Serialize)]
+pub struct CheckReport {
+    /// 检查的密码个数
+    pub total: usize,
+    /// 不合格的个数
+    pub failed: usize,
+    pub results: Vec<PasswordCheck>,
+}
+
+/// 检查选项
+#[derive(Debug, Clone)]
+pub struct CheckOptions<'a> {
+    /// 输入文件 每行一个密码，-为标准输入
+    pub input: &'a str,
+    pub output: &'a str,
+    pub format: ReportFormat,
+    /// 是否把容易混淆的字符算作违规 它们只是生成时的偏好，并不会让密码变弱
+    pub check_ambiguous: bool,
+}
+
+/// 检查并输出报告
+pub fn process_pass_check(policy: &PasswordPolicy, opts: &CheckOptions) -> Result<CheckReport> {
+    let passwords = read_passwords(opts.input)?;
+    let report = check_passwords(&passwords, policy, opts.check_ambiguous)?;
+    let mut writer = get_writer(opts.output)?;
+    match opts.format {
+        ReportFormat::Text => {
+            for check in &report.results {
+                let status = match check.passed() {
+                    true => "ok",
+                    false => "FAIL",
+                };
+                writeln!(
+                    writer,
+                    "line {}: {}, score {}, {:.2} bits",
+                    check.line, status, check.score, check.entropy
+                )?;
+                let times = &check.crack_times;
+                writeln!(
+                    writer,
+                    "  crack time: online throttled {}, online {}, offline slow hash {}, offline fast hash {}",
+                    times.online_throttled,
+                    times.online_unthrottled,
+                    times.offline_slow_hash,
+                    times.offline_fast_hash
+                )?;
+                if let Some(warning) = &check.warning {
+                    writeln!(writer, "  warning: {}", warning)?;
+                }
+                for suggestion in &check.suggestions {
+                    writeln!(writer, "  suggestion: {}", suggestion)?;
+                }
+                for violation in &check.violations {
+                    writeln!(writer, "  violation: {}", violation)?;
+                }
+            }
+            writeln!(
+                writer,
+                "{} of {} passwords failed",
+                report.failed, report.total
+            )?;
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writeln!(writer)?;
+        }
+    }
+    writer.flush()?;
+    Ok(report)
+}
+
+/// 检查每个密码 空行跳过但保留行号
+/// 容易混淆的字符只在check_ambiguous时检查，与策略中的exclude_ambiguous无关
+pub fn check_passwords(
+    passwords: &[String],
+    policy: &PasswordPolicy,
+    check_ambiguous: bool,
+) -> Result<CheckReport> {
+    let policy = &PasswordPolicy {
+        exclude_ambiguous: check_ambiguous,
+        ..policy.clone()
+    };
+    let pwned = policy
+        .pwned_file
+        .as_deref()
+        .map(PwnedPasswords::open)
+        .transpose()?;
+    let mut report = CheckReport::default();
+    for (i, password) in passwords.iter().enumerate() {
+        if password.is_empty() {
+            continue;
+        }
+        let entropy = zxcvbn(password, &[])?;
+        let times = entropy.crack_times();
+        let (warning, suggestions) = match entropy.feedback() {
+            Some(feedback) => (
+                feedback.warning().map(|w| w.to_string()),
+                feedback
+                    .suggestions()
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            None => (None, vec![]),
+        };
+        let mut violations = policy.violations(password, &entropy);
+        let count = match &pwned {
+            Some(pwned) => pwned.count(password)?,
+            None => None,
+        };
+        if let Some(count) = count {
+            violations.push(format!("found {} times in pwned passwords", count));
+        }
+        let check = PasswordCheck {
+            line: i + 1,
+            score: entropy.score(),
+            entropy: entropy_bits(&entropy),
+            crack_times: CrackTimes {
+                online_throttled: times.online_throttling_100_per_hour().to_string(),
+                online_unthrottled: times.online_no_throttling_10_per_second().to_string(),
+                offline_slow_hash: times.offline_slow_hashing_1e4_per_second().to_string(),
+                offline_fast_hash: times.offline_fast_hashing_1e10_per_second().to_string(),
+            },
+            warning,
+            suggestions,
+            pwned: count,
+            violations,
+        };
+        report.total += 1;
+        if !check.passed() {
+            report.failed += 1;
+        }
+        report.results.push(check);
+    }
+    Ok(report)
+}
+
+// 每行一个密码 在终端中输入时不回显，输入空行结束
+fn read_passwords(input: &str) -> Result<Vec<String>> {
+    if input == "-" && std::io::stdin().is_terminal() {
+        let mut passwords = vec![];
+        loop {
+            let password = rpassword::prompt_password("Password (empty line to finish): ")?;
+            if password.is_empty() {
+                return Ok(passwords);
+            }
+            passwords.push(password);
+        }
+    }
+    BufReader::new(get_reader(input)?)
+        .lines()
+        .map(|line| Ok(line?.trim_end_matches('\r').to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_passwords() -> Result<()> {
+        let passwords = read_passwords("fixtures/passwords.txt")?;
+        let report = check_passwords(&passwords, &PasswordPolicy::default(), false)?;
+        assert_eq!(report.total, 3);
+        assert_eq!(report.failed, 1);
+
+        let weak = &report.results[0];
+        assert_eq!(weak.line, 1);
+        assert_eq!(weak.score, 0);
+        assert!(weak.warning.is_some());
+        assert!(weak
+            .violations
+            .contains(&"length 8 is shorter than 16".to_string()));
+        assert!(weak
+            .violations
+            .contains(&"has 0 upper characters, requires at least 1".to_string()));
+
+        assert!(!weak
+            .violations
+            .contains(&"contains ambiguous characters".to_string()));
+
+        // 空行跳过 行号不变，容易混淆的字符默认不算违规
+        let ambiguous = &report.results[1];
+        assert_eq!(ambiguous.line, 3);
+        assert!(ambiguous.passed());
+        assert!(report.results[2].passed());
+        let report = check_passwords(&passwords, &PasswordPolicy::default(), true)?;
+        assert_eq!(
+            report.results[1].violations,
+            vec!["contains ambiguous characters".to_string()]
+        );
+
+        let policy = PasswordPolicy {
+            pwned_file: Some("fixtures/pwned/range".to_string()),
+            ..Default::default()
+        };
+        let report = check_passwords(&passwords, &policy, false)?;
+        assert_eq!(report.results[0].pwned, Some(10434004));
+        assert!(report.results[0]
+            .violations
+            .contains(&"found 10434004 times in pwned passwords".to_string()));
+        assert_eq!(report.results[2].pwned, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_file_rules() -> Result<()> {
+        let policy = PasswordPolicy::load("fixtures/password_policies.toml", "bank")?;
+        let passwords = vec!["AB23!#CC".to_string(), "AAA2!#cd".to_string()];
+        let report = check_passwords(&passwords, &policy, false)?;
+        assert!(report.results[0].passed());
+        assert_eq!(
+            report.results[1].violations,
+            vec![
+                "contains lower characters",
+                "contains not allowed characters",
+                "repeats a character 3 times in a row, max 2",
+            ]
+        );
+        Ok(())
+    }
+}
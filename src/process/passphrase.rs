@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use rand::prelude::*;
+
+use super::{gen_pass::MAX_ATTEMPTS, pwned::PwnedPasswords};
+
+/// 内置的EFF大词表 7776个词，每行为骰子点数和单词
+const EFF_LARGE_WORDLIST: &str = include_str!("../../assets/eff_large_wordlist.txt");
+/// 插入的数字
+const DIGITS: &[u8] = b"0123456789";
+/// 插入的特殊字符
+const SYMBOLS: &[u8] = b"!@#$%^&*_-";
+
+/// 生成的口令及其熵
+#[derive(Debug, Clone)]
+pub struct Passphrase {
+    pub phrase: String,
+    /// 按生成方式精确计算的熵 单位为bit
+    pub entropy: f64,
+}
+
+/// 口令生成选项
+#[derive(Debug, Clone)]
+pub struct PassphraseOptions<'a> {
+    /// 单词个数
+    pub words: usize,
+    /// 自定义词表文件 不指定时使用EFF大词表
+    pub wordlist: Option<&'a str>,
+    pub separator: &'a str,
+    /// 每个单词首字母大写
+    pub capitalize: bool,
+    /// 在随机一个单词后加上一位数字
+    pub add_number: bool,
+    /// 在随机一个单词后加上一个特殊字符
+    pub add_special: bool,
+    /// 本地的HIBP泄露密码文件 生成的口令出现在其中时重新生成
+    pub pwned_file: Option<&'a str>,
+}
+
+/// 生成Diceware口令 每个单词从词表中均匀随机选取
+pub fn process_gen_passphrase(opts: &PassphraseOptions) -> Result<Passphrase> {
+    let content = match opts.wordlist {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => EFF_LARGE_WORDLIST.to_string(),
+    };
+    let mut wordlist: Vec<String> = parse_wordlist(&content)?
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    // 首字母大写后 Apple 和 apple 相同，需要再次去重，否则熵会被高估
+    if opts.capitalize {
+        let mut seen = HashSet::new();
+        wordlist = wordlist
+            .iter()
+            .map(|word| capitalize(word))
+            .filter(|word| seen.insert(word.clone()))
+            .collect();
+        check_wordlist_len(wordlist.len())?;
+    }
+    let pwned = opts.pwned_file.map(PwnedPasswords::open).transpose()?;
+    let mut rng = rand::thread_rng();
+    // 泄露的口令在所有可能的口令中占比极小 拒绝后熵的变化可以忽略
+    for _ in 0..MAX_ATTEMPTS {
+        let passphrase = pick(&wordlist, opts, &mut rng);
+        match &pwned {
+            Some(pwned) if pwned.count(&passphrase.phrase)?.is_some() => continue,
+            _ => return Ok(passphrase),
+        }
+    }
+    Err(anyhow::anyhow!(
+        "Could not generate a passphrase missing from pwned passwords after {} attempts, try more words",
+        MAX_ATTEMPTS
+    ))
+}
+
+// 从词表中选词组成口令 并按生成方式计算熵
+fn pick(wordlist: &[String], opts: &PassphraseOptions, rng: &mut impl Rng) -> Passphrase {
+    let mut words: Vec<String> = (0..opts.words)
+        .map(|_| wordlist.choose(rng).expect("词表不可为空").clone())
+        .collect();
+    // 首字母大写和分隔符是固定的 不增加熵
+    let mut entropy = opts.words as f64 * (wordlist.len() as f64).log2();
+    for (enabled, charset) in [(opts.add_number, DIGITS), (opts.add_special, SYMBOLS)] {
+        if enabled {
+            let word = rng.gen_range(0..words.len());
+            words[word].push(*charset.choose(rng).expect("字符集不可为空") as char);
+            entropy += (charset.len() as f64).log2() + (words.len() as f64).log2();
+        }
+    }
+
+    Passphrase {
+        phrase: words.join(opts.separator),
+        entropy,
+    }
+}
+
+/// 解析词表 每行取最后一列，兼容EFF的 "11111\tabacus" 格式，忽略空行和#开头的行
+/// 重复的词只保留一个 否则熵会被高估
+pub fn parse_wordlist(content: &str) -> Result<Vec<&str>> {
+    let mut seen = HashSet::new();
+    let words: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_whitespace().last())
+        .filter(|word| seen.insert(*word))
+        .collect();
+    check_wordlist_len(words.len())?;
+    Ok(words)
+}
+
+fn check_wordlist_len(len: usize) -> Result<()> {
+    if len < 2 {
+        return Err(anyhow::anyhow!(
+            "Wordlist needs at least 2 distinct words, found {}",
+            len
+        ));
+    }
+    Ok(())
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(words: usize) -> PassphraseOptions<'static> {
+        PassphraseOptions {
+            words,
+            wordlist: None,
+            separator: "-",
+            capitalize: false,
+            add_number: false,
+            add_special: false,
+            pwned_file: None,
+        }
+    }
+
+    #[test]
+    fn test_eff_passphrase() -> Result<()> {
+        let wordlist = parse_wordlist(EFF_LARGE_WORDLIST)?;
+        assert_eq!(wordlist.len(), 7776);
+        assert_eq!(wordlist[0], "abacus");
+
+        let passphrase = process_gen_passphrase(&options(6))?;
+        let words: Vec<&str> = passphrase.phrase.split('-').collect();
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|w| wordlist.contains(w)));
+        assert!((passphrase.entropy - 77.548).abs() < 0.001);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_wordlist_and_injection() -> Result<()> {
+        let passphrase = process_gen_passphrase(&PassphraseOptions {
+            wordlist: Some("fixtures/wordlist.txt"),
+            separator: " ",
+            capitalize: true,
+            add_number: true,
+            add_special: true,
+            ..options(4)
+        })?;
+        let words: Vec<&str> = passphrase.phrase.split(' ').collect();
+        assert_eq!(words.len(), 4);
+        assert!(words.iter().all(|w| w.starts_with(char::is_uppercase)));
+        assert_eq!(
+            passphrase
+                .phrase
+                .chars()
+                .filter(char::is_ascii_digit)
+                .count(),
+            1
+        );
+        // 4个词各2bit 数字log2(10)+2 特殊字符log2(10)+2
+        let expected = 8.0 + 2.0 * (10f64.log2() + 2.0);
+        assert!((passphrase.entropy - expected).abs() < 1e-9);
+
+        assert_eq!(
+            parse_wordlist(&std::fs::read_to_string("fixtures/wordlist.txt")?)?,
+            vec!["apple", "banana", "cherry", "durian"]
+        );
+        assert!(parse_wordlist("only\nonly\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_capitalize_dedup_entropy() -> Result<()> {
+        let opts = PassphraseOptions {
+            wordlist: Some("fixtures/wordlist_case.txt"),
+            ..options(3)
+        };
+        // 不大写时4个词各不相同
+        let passphrase = process_gen_passphrase(&opts)?;
+        assert!((passphrase.entropy - 6.0).abs() < 1e-9);
+        // 大写后只剩 Apple Banana Cherry 三个词
+        let passphrase = process_gen_passphrase(&PassphraseOptions {
+            capitalize: true,
+            ..opts
+        })?;
+        assert!((passphrase.entropy - 3.0 * 3f64.log2()).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pwned_passphrase_is_regenerated() -> Result<()> {
+        // 词表中的 a 和 qwerty 都已泄露 只能生成 b
+        let opts = PassphraseOptions {
+            wordlist: Some("fixtures/wordlist_pwned.txt"),
+            pwned_file: Some("fixtures/pwned/pwned-sha1.txt"),
+            ..options(1)
+        };
+        for _ in 0..20 {
+            assert_eq!(process_gen_passphrase(&opts)?.phrase, "b");
+        }
+        let opts = PassphraseOptions {
+            pwned_file: Some("fixtures/pwned/missing.txt"),
+            ..opts
+        };
+        assert!(process_gen_passphrase(&opts).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use csv::StringRecord;
+
+use super::csv_reader::column_index;
+
+/// 列投影 选择、排除、重命名和重新排序
+#[derive(Debug, Clone)]
+pub struct Projection {
+    /// 输出列在原始记录中的下标
+    indices: Vec<usize>,
+    /// 输出列名
+    headers: StringRecord,
+}
+
+impl Projection {
+    /// 根据选项创建投影 列可以用名称或从1开始的序号指定
+    pub fn new(
+        headers: &StringRecord,
+        select: &[String],
+        exclude: &[String],
+        rename: &[(String, String)],
+    ) -> Result<Self> {
+        // 先选择 没有指定时保留全部列
+        let mut indices = if select.is_empty() {
+            (0..headers.len()).collect::<Vec<_>>()
+        } else {
+            select
+                .iter()
+                .map(|c| column_index(headers, c))
+                .collect::<Result<_>>()?
+        };
+        // 再排除
+        let excluded = exclude
+            .iter()
+            .map(|c| column_index(headers, c))
+            .collect::<Result<HashSet<_>>>()?;
+        indices.retain(|idx| !excluded.contains(idx));
+
+        // 最后重命名
+        let mut names: Vec<String> = indices.iter().map(|&i| headers[i].to_string()).collect();
+        for (old, new) in rename {
+            let idx = column_index(headers, old)?;
+            let pos = indices.iter().position(|&i| i == idx).ok_or_else(|| {
+                anyhow::anyhow!("Cannot rename column {}, it is not selected", old)
+            })?;
+            names[pos] = new.clone();
+        }
+
+        let mut seen = HashSet::new();
+        if let Some(dup) = names.iter().find(|name| !seen.insert(name.as_str())) {
+            return Err(anyhow::anyhow!("Duplicate output column: {}", dup));
+        }
+
+        Ok(Self {
+            indices,
+            headers: StringRecord::from(names),
+        })
+    }
+
+    /// 输出列名
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    /// 输出列在原始记录中的下标
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// 对一行数据进行投影 保留原始的行号信息
+    pub fn apply(&self, record: &StringRecord) -> StringRecord {
+        let mut projected: StringRecord = self
+            .indices
+            .iter()
+            .map(|&i| record.get(i).unwrap_or_default())
+            .collect();
+        projected.set_position(record.position().cloned());
+        projected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projection() -> Result<()> {
+        let headers = StringRecord::from(vec!["Name", "Position", "DOB", "Nationality"]);
+        let row = StringRecord::from(vec!["Buffon", "Goalkeeper", "1978", "Italy"]);
+
+        let projection = Projection::new(
+            &headers,
+            &["Nationality".into(), "1".into(), "DOB".into()],
+            &["DOB".into()],
+            &[("Name".into(), "player".into())],
+        )?;
+        assert_eq!(projection.headers(), &vec!["Nationality", "player"]);
+        assert_eq!(projection.apply(&row), vec!["Italy", "Buffon"]);
+
+        let projection = Projection::new(&headers, &[], &["2".into()], &[])?;
+        assert_eq!(projection.headers().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_projection_errors() {
+        let headers = StringRecord::from(vec!["a", "b"]);
+        assert!(Projection::new(&headers, &["c".into()], &[], &[]).is_err());
+        assert!(Projection::new(&headers, &["3".into()], &[], &[]).is_err());
+        assert!(
+            Projection::new(&headers, &["a".into()], &[], &[("b".into(), "x".into())]).is_err()
+        );
+        assert!(Projection::new(&headers, &[], &[], &[("a".into(), "b".into())]).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use sha1::{Digest, Sha1};
+
+/// 本地的Have I Been Pwned SHA-1哈希文件
+/// 可以是按哈希排序的完整文件，每行为 "哈希:次数"；
+/// 也可以是按前缀下载的目录，每个前缀一个文件如 21BD1.txt，每行为 "后35位:次数"
+/// 文件中的行按哈希排序，查找时二分查找，不需要读入内存
+#[derive(Debug, Clone)]
+pub struct PwnedPasswords {
+    path: PathBuf,
+}
+
+/// 哈希前缀的长度 与HIBP的range接口一致
+const PREFIX_LEN: usize = 5;
+
+impl PwnedPasswords {
+    pub fn open(path: &str) -> Result<Self> {
+        let path = PathBuf::from(path);
+        if !path.exists() {
+            return Err(anyhow::anyhow!(
+                "Pwned passwords file not found: {}",
+                path.display()
+            ));
+        }
+        Ok(Self { path })
+    }
+
+    /// 密码在泄露数据中出现的次数 未出现时为None
+    pub fn count(&self, password: &str) -> Result<Option<u64>> {
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        if self.path.is_dir() {
+            let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+            let path = self.path.join(format!("{}.txt", prefix));
+            // 没有下载的前缀视为未泄露
+            if !path.exists() {
+                return Ok(None);
+            }
+            search_sorted(&path, suffix)
+        } else {
+            search_sorted(&self.path, &hash)
+        }
+    }
+}
+
+// 在按哈希排序的文件中二分查找
+// lo始终是行首，要找的行如果存在，一定从[lo, hi)中开始
+fn search_sorted(path: &Path, hash: &str) -> Result<Option<u64>> {
+    let file = File::open(path)?;
+    let (mut lo, mut hi) = (0, file.metadata()?.len());
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        // 从mid开始的第一个行首 mid的前一个字符是换行符时mid就是行首
+        let start = match mid {
+            0 => 0,
+            _ => {
+                reader.seek(SeekFrom::Start(mid - 1))?;
+                let mut skipped = vec![];
+                mid - 1 + reader.read_until(b'\n', &mut skipped)? as u64
+            }
+        };
+        if start >= hi {
+            hi = mid;
+            continue;
+        }
+        reader.seek(SeekFrom::Start(start))?;
+        line.clear();
+        let len = reader.read_line(&mut line)? as u64;
+        let (key, count) = parse_line(&line)?;
+        match key.to_ascii_uppercase().as_str().cmp(hash) {
+            std::cmp::Ordering::Equal => return Ok(Some(count)),
+            std::cmp::Ordering::Less => lo = start + len,
+            std::cmp::Ordering::Greater => hi = start,
+        }
+    }
+    Ok(None)
+}
+
+fn parse_line(line: &str) -> Result<(&str, u64)> {
+    let line = line.trim_end();
+    let (hash, count) = line
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Invalid pwned passwords line: {}", line))?;
+    let count = count
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid pwned passwords line: {}", line))?;
+    Ok((hash, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pwned_file_and_range_dir() -> Result<()> {
+        // fixtures中有 password 123456 qwerty a 以及随机生成的填充行
+        let path = Path::new("fixtures/pwned/pwned-sha1.txt");
+        for line in std::fs::read_to_string(path)?.lines() {
+            let (hash, count) = parse_line(line)?;
+            assert_eq!(search_sorted(path, hash)?, Some(count));
+            assert_eq!(search_sorted(path, &format!("{}0", hash))?, None);
+        }
+        assert_eq!(search_sorted(path, &"F".repeat(40))?, None);
+
+        let pwned = PwnedPasswords::open("fixtures/pwned/pwned-sha1.txt")?;
+        assert_eq!(pwned.count("password")?, Some(10434004));
+        assert_eq!(pwned.count("123456")?, Some(37359195));
+        assert_eq!(pwned.count("qwerty")?, Some(10556095));
+        assert_eq!(pwned.count("Kx9#mQ2$vR7!tZ4w")?, None);
+
+        let pwned = PwnedPasswords::open("fixtures/pwned/range")?;
+        assert_eq!(pwned.count("password")?, Some(10434004));
+        assert_eq!(pwned.count("passw0rd")?, None);
+        assert_eq!(pwned.count("Kx9#mQ2$vR7!tZ4w")?, None);
+        assert!(PwnedPasswords::open("fixtures/pwned/missing.txt").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::Read;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::{cli::OutputFormat, get_reader};
+
+/// 记录迭代器
+pub type Records = Box<dyn Iterator<Item = Result<Value>>>;
+
+/// 根据格式读取结构化数据中的记录
+pub fn read_records(format: OutputFormat, input: &str) -> Result<Records> {
+    records_from_reader(format, get_reader(input)?)
+}
+
+/// 从Reader中读取记录 由格式的实现决定流式读取还是整体解析
+pub fn records_from_reader(format: OutputFormat, reader: Box<dyn Read>) -> Result<Records> {
+    format.reader(reader)
+}
+
+/// 根据文件后缀推断格式
+pub fn detect_format(input: &str) -> Result<OutputFormat> {
+    let ext = std::path::Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Cannot detect format of {}, use --from", input))?;
+    ext.parse()
+}
+
+/// 从文档中取出记录列表
+/// 支持顶层数组、只有一个数组字段的对象(如 rcli csv 输出的toml)，以及单个对象
+pub fn document_records(document: Value) -> Vec<Value> {
+    match document {
+        Value::Array(items) => items,
+        Value::Object(map) if map.len() == 1 && map.values().all(Value::is_array) => {
+            match map.into_iter().next() {
+                Some((_, Value::Array(items))) => items,
+                _ => unreachable!(),
+            }
+        }
+        Value::Null => vec![],
+        document => vec![document],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn read(format: OutputFormat, content: &'static str) -> Result<Vec<Value>> {
+        records_from_reader(format, Box::new(content.as_bytes()))?.collect()
+    }
+
+    #[test]
+    fn test_read_records() -> Result<()> {
+        let expected = vec![json!({"a": 1}), json!({"a": 2})];
+        assert_eq!(read(OutputFormat::JSON, r#"[{"a":1},{"a":2}]"#)?, expected);
+        assert_eq!(
+            read(OutputFormat::NDJSON, "{\"a\":1}\n{\"a\":2}\n")?,
+            expected
+        );
+        assert_eq!(read(OutputFormat::YAML, "- a: 1\n- a: 2\n")?, expected);
+        assert_eq!(
+            read(OutputFormat::TOML, "[[data]]\na = 1\n\n[[data]]\na = 2\n")?,
+            expected
+        );
+        assert_eq!(
+            read(OutputFormat::JSON, r#"{"a":1}"#)?,
+            vec![json!({"a": 1})]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format("a.yml").ok(), Some(OutputFormat::YAML));
+        assert_eq!(detect_format("a.jsonl").ok(), Some(OutputFormat::NDJSON));
+        assert!(detect_format("-").is_err());
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::cli::OutputFormat;
+
+use super::format::FormatOptions;
+
+/// 定义逐条写出记录的Trait 内存占用与输入大小无关
+pub trait RecordWriter {
+    /// 写入一条记录
+    fn write(&mut self, record: &Value) -> Result<()>;
+    /// 写入结尾并刷新
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// 根据输出格式创建RecordWriter 使用默认的输出选项
+pub fn record_writer(format: OutputFormat, output: &str) -> Result<Box<dyn RecordWriter>> {
+    format.writer(output, &FormatOptions::default())
+}
+
+/// Json数组 与 serde_json::to_string_pretty 的输出一致
+pub struct JsonWriter<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl<W: Write> RecordWriter for JsonWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.writer
+            .write_all(if self.count == 0 { b"[\n" } else { b",\n" })?;
+        // 数组元素需要多缩进一层
+        let content = serde_json::to_string_pretty(record)?;
+        write_indented(&mut self.writer, &content, "  ", "  ")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer
+            .write_all(if self.count == 0 { b"[]" } else { b"\n]" })?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 每行一个Json对象
+pub struct NdjsonWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> RecordWriter for NdjsonWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Yaml序列 与 serde_yaml::to_string 的输出一致
+pub struct YamlWriter<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> YamlWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl<W: Write> RecordWriter for YamlWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        // 第一行以 "- " 开头 其余行缩进两格
+        let content = serde_yaml::to_string(record)?;
+        write_indented(&mut self.writer, content.trim_end(), "- ", "  ")?;
+        self.writer.write_all(b"\n")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.writer.write_all(b"[]\n")?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Toml 以 [[data]] 数组表的形式逐条追加
+pub struct TomlWriter<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> TomlWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl<W: Write> RecordWriter for TomlWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        if self.count > 0 {
+            self.writer.write_all(b"\n")?;
+        }
+        // Toml没有空值 直接省略
+        let record = drop_nulls(record);
+        let content = toml::to_string_pretty(&HashMap::from([("data", [record])]))?;
+        self.writer.write_all(content.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// CSV 列名取自第一条记录
+pub struct CsvWriter<W: Write> {
+    writer: csv::Writer<W>,
+    headers: Option<Vec<String>>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: csv::Writer::from_writer(writer),
+            headers: None,
+        }
+    }
+}
+
+impl<W: Write> RecordWriter for CsvWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let Value::Object(map) = record else {
+            return Err(anyhow::anyhow!(
+                "CSV output requires objects, got {}",
+                record
+            ));
+        };
+        let headers = match &self.headers {
+            Some(headers) => headers,
+            None => {
+                let headers: Vec<String> = map.keys().cloned().collect();
+                self.writer.write_record(&headers)?;
+                self.headers.insert(headers)
+            }
+        };
+        // 缺少的列输出为空
+        let row = headers.iter().map(|h| cell_text(map.get(h)));
+        self.writer.write_record(row)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// MessagePack数组 数组头中需要记录数
+/// 记录先逐条编码到临时文件，finish时写出数组头再复制，内存占用与记录数无关
+pub struct MsgpackWriter<W: Write> {
+    writer: W,
+    spool: BufWriter<File>,
+    count: u32,
+}
+
+impl<W: Write> MsgpackWriter<W> {
+    pub fn new(writer: W) -> Result<Self> {
+        Ok(Self {
+            writer,
+            spool: BufWriter::new(tempfile::tempfile()?),
+            count: 0,
+        })
+    }
+}
+
+impl<W: Write> RecordWriter for MsgpackWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.count = self.count.checked_add(1).ok_or_else(|| {
+            anyhow::anyhow!("MessagePack arrays hold at most {} records", u32::MAX)
+        })?;
+        rmp_serde::encode::write(&mut self.spool, record)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        rmp::encode::write_array_len(&mut self.writer, self.count)?;
+        self.spool.flush()?;
+        let spool = self.spool.get_mut();
+        spool.seek(SeekFrom::Start(0))?;
+        std::io::copy(spool, &mut self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// CBOR不定长数组 逐条写出，以break结尾
+pub struct CborWriter<W: Write> {
+    writer: W,
+    started: bool,
+}
+
+/// 不定长数组的开头和结尾
+const CBOR_ARRAY_START: u8 = 0x9f;
+const CBOR_BREAK: u8 = 0xff;
+
+impl<W: Write> CborWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            started: false,
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if !self.started {
+            self.writer.write_all(&[CBOR_ARRAY_START])?;
+            self.started = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for CborWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.start()?;
+        ciborium::into_writer(record, &mut self.writer)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.start()?;
+        self.writer.write_all(&[CBOR_BREAK])?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 表格单元格的文本 缺少的列和空值为空，嵌套的值为Json文本
+pub fn cell_text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+// 按行写入内容 第一行与其余行使用不同的前缀
+fn write_indented(writer: &mut impl Write, content: &str, first: &str, rest: &str) -> Result<()> {
+    for (i, line) in content.lines().enumerate() {
+        if i > 0 {
+            writer.write_all(b"\n")?;
+        }
+        // 空行不加缩进 避免出现行尾空格
+        if !line.is_empty() {
+            writer.write_all(if i == 0 { first } else { rest }.as_bytes())?;
+        }
+        writer.write_all(line.as_bytes())?;
+    }
+    Ok(())
+}
+
+// 递归去掉空值
+fn drop_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k.clone(), drop_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .filter(|v| !v.is_null())
+                .map(drop_nulls)
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({"Name": "Alice", "Age": 30, "Tags": ["a", "b"]}),
+            json!({"Name": "Bob", "Age": null, "Tags": []}),
+        ]
+    }
+
+    fn write_all(writer: &mut dyn RecordWriter, records: &[Value]) -> Result<()> {
+        for record in records {
+            writer.write(record)?;
+        }
+        writer.finish()
+    }
+
+    #[test]
+    fn test_json_writer_matches_pretty() -> Result<()> {
+        for records in [records(), vec![]] {
+            let mut buf = Vec::new();
+            write_all(&mut JsonWriter::new(&mut buf), &records)?;
+            assert_eq!(
+                String::from_utf8(buf)?,
+                serde_json::to_string_pretty(&records)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_writer_matches_sequence() -> Result<()> {
+        for records in [records(), vec![]] {
+            let mut buf = Vec::new();
+            write_all(&mut YamlWriter::new(&mut buf), &records)?;
+            assert_eq!(String::from_utf8(buf)?, serde_yaml::to_string(&records)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_writer() -> Result<()> {
+        let mut buf = Vec::new();
+        write_all(&mut CsvWriter::new(&mut buf), &records())?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "Name,Age,Tags\nAlice,30,\"[\"\"a\"\",\"\"b\"\"]\"\nBob,,[]\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ndjson_and_toml_writer() -> Result<()> {
+        let mut buf = Vec::new();
+        write_all(&mut NdjsonWriter::new(&mut buf), &records())?;
+        let lines = String::from_utf8(buf)?;
+        assert_eq!(lines.lines().count(), 2);
+        assert_eq!(
+            serde_json::from_str::<Value>(lines.lines().next().unwrap())?,
+            records()[0]
+        );
+
+        let mut buf = Vec::new();
+        write_all(&mut TomlWriter::new(&mut buf), &records())?;
+        let doc: toml::Value = toml::from_str(&String::from_utf8(buf)?)?;
+        let data = doc["data"].as_array().unwrap();
+        assert_eq!(data.len(), 2);
+        assert!(data[1].get("Age").is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::Write;
+
+use anyhow::Result;
+use csv::StringRecord;
+use serde_json::{Map, Value};
+
+use crate::{cli::OutputFormat, get_writer};
+
+use super::record_writer::{record_writer, RecordWriter};
+
+/// 逐行写出CSV行 不指定格式时按给定的分隔符输出CSV，否则转换为记录
+pub struct RowWriter {
+    headers: StringRecord,
+    sink: RowSink,
+}
+
+enum RowSink {
+    Csv(Box<csv::Writer<Box<dyn Write>>>),
+    Records(Box<dyn RecordWriter>),
+}
+
+impl RowWriter {
+    /// 创建时立即写出CSV的Header
+    pub fn new(
+        output: &str,
+        format: Option<OutputFormat>,
+        headers: &StringRecord,
+        delimiter: char,
+    ) -> Result<Self> {
+        let sink = match format {
+            Some(format) => RowSink::Records(record_writer(format, output)?),
+            None => {
+                let mut writer = csv::WriterBuilder::new()
+                    .delimiter(delimiter as u8)
+                    .from_writer(get_writer(output)?);
+                writer.write_record(headers)?;
+                RowSink::Csv(Box::new(writer))
+            }
+        };
+        Ok(Self {
+            headers: headers.clone(),
+            sink,
+        })
+    }
+
+    /// 写入一行
+    pub fn write(&mut self, row: &StringRecord) -> Result<()> {
+        match &mut self.sink {
+            RowSink::Csv(writer) => writer.write_record(row)?,
+            RowSink::Records(writer) => {
+                let record: Map<String, Value> = self
+                    .headers
+                    .iter()
+                    .zip(row.iter())
+                    .map(|(h, v)| (h.to_string(), Value::from(v)))
+                    .collect();
+                writer.write(&Value::Object(record))?
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入结尾并刷新
+    pub fn finish(&mut self) -> Result<()> {
+        match &mut self.sink {
+            RowSink::Csv(writer) => writer.flush()?,
+            RowSink::Records(writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use std::io::Write;
+
+use anyhow::Result;
+use rusqlite::{params_from_iter, types::Value as SqlValue, Connection};
+use serde_json::Value;
+
+use crate::cli::{ColumnType, SqlDialect};
+
+use super::{csv_infer::merge_type, record_writer::RecordWriter};
+
+/// SQL表定义
+#[derive(Debug, Clone)]
+pub struct SqlTable {
+    /// 表名
+    pub name: String,
+    /// SQL方言
+    pub dialect: SqlDialect,
+    /// 每条INSERT语句包含的行数
+    pub batch_size: usize,
+    /// 列名和类型 为None时根据第一批数据推断
+    pub columns: Option<Vec<(String, ColumnType)>>,
+}
+
+impl Default for SqlTable {
+    fn default() -> Self {
+        Self {
+            name: "data".to_string(),
+            dialect: SqlDialect::Postgres,
+            batch_size: 500,
+            columns: None,
+        }
+    }
+}
+
+impl SqlTable {
+    // 确定列定义 没有预先指定时根据记录中的值推断
+    fn resolve_columns(&mut self, batch: &[Value]) -> &[(String, ColumnType)] {
+        self.columns.get_or_insert_with(|| {
+            let Some(Value::Object(first)) = batch.first() else {
+                return vec![];
+            };
+            first
+                .keys()
+                .map(|key| {
+                    let ty = batch
+                        .iter()
+                        .map(|record| value_type(&record[key]))
+                        .fold(ColumnType::Null, merge_type);
+                    (key.clone(), ty)
+                })
+                .collect()
+        })
+    }
+
+    /// 建表语句
+    pub fn create_statement(&self) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .flatten()
+            .map(|(name, ty)| {
+                format!(
+                    "  {} {}",
+                    quote_ident(self.dialect, name),
+                    column_type(self.dialect, *ty)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!(
+            "CREATE TABLE {} (\n{}\n);\n",
+            quote_ident(self.dialect, &self.name),
+            columns
+        )
+    }
+
+    /// 一批数据的INSERT语句
+    pub fn insert_statement(&self, batch: &[Value]) -> String {
+        let columns = self.columns.as_deref().unwrap_or_default();
+        let names = columns
+            .iter()
+            .map(|(name, _)| quote_ident(self.dialect, name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let rows = batch
+            .iter()
+            .map(|record| {
+                let values = columns
+                    .iter()
+                    .map(|(name, _)| literal(self.dialect, &record[name]))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("  ({})", values)
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!(
+            "INSERT INTO {} ({}) VALUES\n{};\n",
+            quote_ident(self.dialect, &self.name),
+            names,
+            rows
+        )
+    }
+}
+
+/// 输出 CREATE TABLE 和批量 INSERT 语句
+pub struct SqlWriter<W: Write> {
+    writer: W,
+    table: SqlTable,
+    batch: Vec<Value>,
+    created: bool,
+}
+
+impl<W: Write> SqlWriter<W> {
+    pub fn new(writer: W, table: SqlTable) -> Self {
+        Self {
+            writer,
+            table,
+            batch: vec![],
+            created: false,
+        }
+    }
+
+    fn flush_batch(&mut self) -> Result<()> {
+        if !self.created {
+            if self.table.resolve_columns(&self.batch).is_empty() {
+                return Ok(());
+            }
+            self.writer
+                .write_all(self.table.create_statement().as_bytes())?;
+            self.created = true;
+        }
+        if !self.batch.is_empty() {
+            let insert = self.table.insert_statement(&self.batch);
+            self.writer.write_all(b"\n")?;
+            self.writer.write_all(insert.as_bytes())?;
+            self.batch.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for SqlWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.batch.push(record.clone());
+        if self.batch.len() >= self.table.batch_size {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush_batch()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 直接写入SQLite数据库文件 文件已存在时在其中新建表
+pub struct SqliteWriter {
+    conn: Connection,
+    table: SqlTable,
+    batch: Vec<Value>,
+    created: bool,
+}
+
+impl SqliteWriter {
+    pub fn new(path: &str, mut table: SqlTable) -> Result<Self> {
+        if path == "-" {
+            return Err(anyhow::anyhow!("sqlite output requires a file path"));
+        }
+        let conn = Connection::open(path)?;
+        // 整个导入放在一个事务中 速度更快且失败时不会留下一半的数据
+        conn.execute_batch("BEGIN")?;
+        table.dialect = SqlDialect::Sqlite;
+        Ok(Self {
+            conn,
+            table,
+            batch: vec![],
+            created: false,
+        })
+    }
+
+    fn flush_batch(&mut self) -> Result<()> {
+        if !self.created {
+            if self.table.resolve_columns(&self.batch).is_empty() {
+                return Ok(());
+            }
+            self.conn.execute_batch(&self.table.create_statement())?;
+            self.created = true;
+        }
+        let columns = self.table.columns.as_deref().unwrap_or_default();
+        let sql = format!(
+            "INSERT INTO {} VALUES ({})",
+            quote_ident(SqlDialect::Sqlite, &self.table.name),
+            vec!["?"; columns.len()].join(", ")
+        );
+        let mut insert = self.conn.prepare_cached(&sql)?;
+        for record in self.batch.drain(..) {
+            insert.execute(params_from_iter(
+                columns.iter().map(|(name, _)| sqlite_value(&record[name])),
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+impl RecordWriter for SqliteWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.batch.push(record.clone());
+        if self.batch.len() >= self.table.batch_size {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush_batch()?;
+        self.conn.execute_batch("COMMIT")?;
+        Ok(())
+    }
+}
+
+/// Json值转换为SQLite的值 嵌套结构保存为Json文本
+pub fn sqlite_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+// 根据Json值判断类型 字符串不再细分日期
+fn value_type(value: &Value) -> ColumnType {
+    match value {
+        Value::Null => ColumnType::Null,
+        Value::Bool(_) => ColumnType::Bool,
+        Value::Number(n) if n.is_i64() => ColumnType::Int,
+        Value::Number(_) => ColumnType::Float,
+        _ => ColumnType::String,
+    }
+}
+
+// 列类型对应的SQL类型
+fn column_type(dialect: SqlDialect, ty: ColumnType) -> &'static str {
+    use SqlDialect::*;
+    match (dialect, ty) {
+        (Sqlite, ColumnType::Int | ColumnType::Bool) => "INTEGER",
+        (Sqlite, ColumnType::Float) => "REAL",
+        // SQLite没有日期类型 使用ISO-8601文本
+        (Sqlite, _) => "TEXT",
+        (_, ColumnType::Int) => "BIGINT",
+        (Postgres, ColumnType::Float) => "DOUBLE PRECISION",
+        (Mysql, ColumnType::Float) => "DOUBLE",
+        (_, ColumnType::Bool) => "BOOLEAN",
+        (_, ColumnType::Date) => "DATE",
+        (_, ColumnType::Time) => "TIME",
+        (Postgres, ColumnType::DateTime) => "TIMESTAMP",
+        (Mysql, ColumnType::DateTime) => "DATETIME",
+        (_, ColumnType::String | ColumnType::Null) => "TEXT",
+    }
+}
+
+// 标识符 MySQL使用反引号，其余使用双引号
+fn quote_ident(dialect: SqlDialect, name: &str) -> String {
+    match dialect {
+        SqlDialect::Mysql => format!("`{}`", name.replace('`', "``")),
+        _ => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+// 值的字面量
+fn literal(dialect: SqlDialect, value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) if dialect == SqlDialect::Sqlite => (*b as u8).to_string(),
+        Value::Bool(b) => b.to_string().to_uppercase(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => quote_str(dialect, s),
+        other => quote_str(dialect, &other.to_string()),
+    }
+}
+
+// 字符串字面量 单引号加倍，MySQL默认还会把反斜杠当作转义符
+fn quote_str(dialect: SqlDialect, s: &str) -> String {
+    let s = s.replace('\'', "''");
+    match dialect {
+        SqlDialect::Mysql => format!("'{}'", s.replace('\\', "\\\\")),
+        _ => format!("'{}'", s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({"Name": "O'Neil", "Age": 30, "Score": 1.5, "Active": true}),
+            json!({"Name": "C:\\x", "Age": null, "Score": 2, "Active": false}),
+            json!({"Name": "Bob", "Age": 41, "Score": 3.0, "Active": null}),
+        ]
+    }
+
+    fn write_sql(table: SqlTable) -> Result<String> {
+        let mut buf = Vec::new();
+        let mut writer = SqlWriter::new(&mut buf, table);
+        for record in records() {
+            writer.write(&record)?;
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn test_sql_writer_dialects() -> Result<()> {
+        let table = SqlTable {
+            batch_size: 2,
+            ..Default::default()
+        };
+        let sql = write_sql(table.clone())?;
+        assert!(sql.starts_with(
+            "CREATE TABLE \"data\" (\n  \"Name\" TEXT,\n  \"Age\" BIGINT,\n  \"Score\" DOUBLE PRECISION,\n  \"Active\" BOOLEAN\n);\n"
+        ));
+        assert_eq!(sql.matches("INSERT INTO").count(), 2);
+        assert!(sql.contains("('O''Neil', 30, 1.5, TRUE),\n  ('C:\\x', NULL, 2, FALSE);"));
+
+        let sql = write_sql(SqlTable {
+            dialect: SqlDialect::Mysql,
+            ..table.clone()
+        })?;
+        assert!(sql.contains("CREATE TABLE `data`"));
+        assert!(sql.contains("'C:\\\\x'"));
+
+        let sql = write_sql(SqlTable {
+            dialect: SqlDialect::Sqlite,
+            ..table
+        })?;
+        assert!(sql.contains("\"Score\" REAL"));
+        assert!(sql.contains("('O''Neil', 30, 1.5, 1)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_writer_runs_in_sqlite() -> Result<()> {
+        let sql = write_sql(SqlTable {
+            dialect: SqlDialect::Sqlite,
+            ..Default::default()
+        })?;
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(&sql)?;
+        let name: String =
+            conn.query_row("SELECT Name FROM data WHERE Name LIKE 'O%'", [], |row| {
+                row.get(0)
+            })?;
+        assert_eq!(name, "O'Neil");
+        Ok(())
+    }
+
+    #[test]
+    fn test_sqlite_writer() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("players.db");
+        let path = path.to_str().unwrap();
+
+        let table = SqlTable {
+            name: "players".to_string(),
+            batch_size: 2,
+            ..Default::default()
+        };
+        let mut writer = SqliteWriter::new(path, table.clone())?;
+        for record in records() {
+            writer.write(&record)?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let conn = Connection::open(path)?;
+        let (count, total): (i64, i64) = conn.query_row(
+            "SELECT count(*), sum(Age) FROM players WHERE Active IS NOT NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!((count, total), (2, 30));
+        // 同名的表已经存在
+        assert!(SqliteWriter::new(path, table)
+            .and_then(|mut w| {
+                w.write(&records()[0])?;
+                w.finish()
+            })
+            .is_err());
+        Ok(())
+    }
+}
@@ -11,7 +11,10 @@ use chacha20poly1305::{
 use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
 
 use super::gen_pass;
-/// 使用多种方式对文本进行签名
+// 使用多种方式对文本进行签名
+
+/// 对称密钥的长度
+const KEY_LEN: usize = 32;
 
 /// 定义签名Trait
 pub trait TextSign {
@@ -50,7 +53,7 @@ pub trait TextDecrypt {
 
 /// Blake3签名
 #[derive(Debug)]
-struct Blake3 {
+pub struct Blake3 {
     key: [u8; 32],
 }
 
@@ -63,17 +66,23 @@ impl Blake3 {
         let key = key.try_into()?;
         Ok(Self::new(key))
     }
+
+    /// 带Key的哈希 相同的输入和Key总是得到相同的结果
+    pub fn keyed_hash(&self, data: &[u8]) -> blake3::Hash {
+        blake3::keyed_hash(&self.key, data)
+    }
 }
 impl KeyLoader for Blake3 {
     fn load_key(key: impl AsRef<Path>) -> Result<Self> {
-        let key = std::fs::read(key)?;
-
-        Self::try_new(&key[..32])
+        Self::try_new(&read_key(key)?)
     }
 }
 impl KeyGenerate for Blake3 {
     fn generate_key() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = gen_pass::process_gen_pass(32, false, false, false, false)?;
+        let key = gen_pass::process_gen_pass(&gen_pass::PasswordPolicy {
+            length: 32,
+            ..Default::default()
+        })?;
         let key = key.as_bytes().to_vec();
 
         Ok(vec![("balke3.txt", key)].into_iter().collect())
@@ -205,32 +214,13 @@ impl Chacha20 {
         let key = chacha20poly1305::Key::from_slice(key);
         Ok(Self::new(key.to_owned()))
     }
-}
-impl KeyLoader for Chacha20 {
-    fn load_key(key: impl AsRef<Path>) -> Result<Self> {
-        let data = std::fs::read(key)?;
-        let key = &data[0..32];
-        Self::try_new(key)
-    }
-}
-
-impl KeyGenerate for Chacha20 {
-    fn generate_key() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = ChaCha20Poly1305::generate_key(&mut OsRng).to_vec();
-        Ok(vec![("chacha20.txt", key)].into_iter().collect())
-    }
-}
-
-impl TextEncrypt for Chacha20 {
-    fn encrypt(&self, reader: &mut dyn std::io::Read) -> Result<String> {
-        let mut buf = String::new();
-        reader.read_to_string(&mut buf)?;
-        let buf = buf.trim().as_bytes();
 
+    /// 加密数据 输出 nonce+密文 的Base64
+    pub fn encrypt_bytes(&self, data: &[u8]) -> Result<String> {
         let cipher = ChaCha20Poly1305::new(&self.key);
         let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
         let cipher_text = cipher
-            .encrypt(&nonce, buf)
+            .encrypt(&nonce, data)
             .map_err(|e| anyhow::anyhow!(e))?;
 
         // 将nonce和cipher_text合并
@@ -241,15 +231,14 @@ impl TextEncrypt for Chacha20 {
         let merged_nonce = URL_SAFE_NO_PAD.encode(merged_nonce);
         Ok(merged_nonce)
     }
-}
 
-impl TextDecrypt for Chacha20 {
-    fn decrypt(&self, reader: &mut dyn std::io::Read) -> Result<String> {
-        let mut buf = String::new();
-        reader.read_to_string(&mut buf)?;
-        let buf = buf.trim();
+    /// 解密 encrypt_bytes 的结果
+    pub fn decrypt_str(&self, data: &str) -> Result<String> {
         // 得先解密
-        let buf = URL_SAFE_NO_PAD.decode(buf)?;
+        let buf = URL_SAFE_NO_PAD.decode(data)?;
+        if buf.len() < 12 {
+            return Err(anyhow::anyhow!("Cipher text is too short"));
+        }
         // 头12位是nonce 之后的是cipher_text
         let nonce = &buf[0..12];
         let cipher_text = &buf[12..];
@@ -264,6 +253,50 @@ impl TextDecrypt for Chacha20 {
         Ok(plain_text)
     }
 }
+impl KeyLoader for Chacha20 {
+    fn load_key(key: impl AsRef<Path>) -> Result<Self> {
+        Self::try_new(&read_key(key)?)
+    }
+}
+
+// 读取对称密钥 只使用文件的前32字节，不足时报错
+fn read_key(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let mut data = std::fs::read(path)?;
+    if data.len() < KEY_LEN {
+        return Err(anyhow::anyhow!(
+            "Key file {} has {} bytes, requires at least {}",
+            path.display(),
+            data.len(),
+            KEY_LEN
+        ));
+    }
+    data.truncate(KEY_LEN);
+    Ok(data)
+}
+
+impl KeyGenerate for Chacha20 {
+    fn generate_key() -> Result<HashMap<&'static str, Vec<u8>>> {
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng).to_vec();
+        Ok(vec![("chacha20.txt", key)].into_iter().collect())
+    }
+}
+
+impl TextEncrypt for Chacha20 {
+    fn encrypt(&self, reader: &mut dyn std::io::Read) -> Result<String> {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf)?;
+        self.encrypt_bytes(buf.trim().as_bytes())
+    }
+}
+
+impl TextDecrypt for Chacha20 {
+    fn decrypt(&self, reader: &mut dyn std::io::Read) -> Result<String> {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf)?;
+        self.decrypt_str(buf.trim())
+    }
+}
 /// 签名逻辑
 pub fn process_text_sign(key: &str, input: &str, format: TextFormat) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
@@ -379,4 +412,12 @@ mod tests {
         assert_eq!(res.as_str(), data);
         Ok(())
     }
+
+    /// 密钥文件不足32字节时报错
+    #[test]
+    fn test_short_key_file() {
+        let err = Chacha20::load_key("fixtures/key_short.txt").unwrap_err();
+        assert!(err.to_string().contains("requires at least 32"), "{}", err);
+        assert!(Blake3::load_key("fixtures/key_short.txt").is_err());
+    }
 }
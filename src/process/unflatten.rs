@@ -0,0 +1,244 @@
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+/// 数组下标的上限 下标来自列名，过大时会分配大量内存
+const MAX_INDEX: usize = 10_000;
+
+/// 路径中的一段
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    /// 对象的字段
+    Key(String),
+    /// 数组的下标
+    Index(usize),
+}
+
+/// 将列名解析为路径 如 address.city 或 tags[0]
+pub fn parse_path(header: &str, separator: &str) -> Vec<PathSegment> {
+    let mut path = vec![];
+    for part in header.split(separator) {
+        match parse_part(part) {
+            // 第一段必须是字段名
+            Some(segments)
+                if !path.is_empty() || matches!(segments.first(), Some(PathSegment::Key(_))) =>
+            {
+                path.extend(segments)
+            }
+            // 无法解析的部分保持原样作为字段名
+            _ => path.push(PathSegment::Key(part.to_string())),
+        }
+    }
+    path
+}
+
+// 解析 name[0][1] 形式的片段
+fn parse_part(part: &str) -> Option<Vec<PathSegment>> {
+    let (name, mut rest) = match part.find('[') {
+        Some(idx) => part.split_at(idx),
+        None => (part, ""),
+    };
+    let mut segments = vec![];
+    if !name.is_empty() {
+        segments.push(PathSegment::Key(name.to_string()));
+    }
+    while !rest.is_empty() {
+        let end = rest.find(']')?;
+        let index = rest.get(1..end)?.parse().ok()?;
+        segments.push(PathSegment::Index(index));
+        rest = &rest[end + 1..];
+        if !rest.is_empty() && !rest.starts_with('[') {
+            return None;
+        }
+    }
+    Some(segments)
+}
+
+/// 将扁平的一行数据按路径还原为嵌套结构
+/// 空值和空字符串不会与对象或数组冲突，其余冲突会报告行号
+pub fn unflatten_row(
+    paths: &[Vec<PathSegment>],
+    row: Map<String, Value>,
+    line: u64,
+) -> Result<Value> {
+    let mut root = Value::Object(Map::new());
+    for (path, (header, value)) in paths.iter().zip(row) {
+        insert_path(&mut root, path, value)
+            .map_err(|e| anyhow::anyhow!("line {}, column {}: {}", line, header, e))?;
+    }
+    Ok(root)
+}
+
+// 按路径插入值
+fn insert_path(root: &mut Value, path: &[PathSegment], value: Value) -> Result<()> {
+    let mut node = root;
+    for (depth, segment) in path.iter().enumerate() {
+        let is_last = depth + 1 == path.len();
+        // 之前插入的空值可以被容器覆盖
+        if is_blank(node) {
+            *node = match segment {
+                PathSegment::Key(_) => Value::Object(Map::new()),
+                PathSegment::Index(_) => Value::Array(vec![]),
+            };
+        }
+        let prefix = describe(&path[..depth]);
+        node = match (segment, node) {
+            (PathSegment::Key(key), Value::Object(map)) => {
+                map.entry(key.clone()).or_insert(Value::Null)
+            }
+            (PathSegment::Index(idx), Value::Array(_)) if *idx > MAX_INDEX => {
+                return Err(anyhow::anyhow!(
+                    "array index {} in {} exceeds the limit of {}",
+                    idx,
+                    describe(&path[..=depth]),
+                    MAX_INDEX
+                ))
+            }
+            (PathSegment::Index(idx), Value::Array(items)) => {
+                if items.len() <= *idx {
+                    items.resize(idx + 1, Value::Null);
+                }
+                &mut items[*idx]
+            }
+            (PathSegment::Key(_), other) => {
+                return Err(anyhow::anyhow!(
+                    "conflicting path, {} is {} but an object is expected",
+                    prefix,
+                    kind(other)
+                ))
+            }
+            (PathSegment::Index(_), other) => {
+                return Err(anyhow::anyhow!(
+                    "conflicting path, {} is {} but an array is expected",
+                    prefix,
+                    kind(other)
+                ))
+            }
+        };
+        if is_last {
+            match (&*node, &value) {
+                (Value::Null, _) => *node = value,
+                // 空值不覆盖已有的对象或数组
+                (Value::Object(_) | Value::Array(_), value) if is_blank(value) => {}
+                (existing, _) => {
+                    return Err(anyhow::anyhow!(
+                        "conflicting path, {} is already {}",
+                        describe(path),
+                        kind(existing)
+                    ))
+                }
+            }
+            break;
+        }
+    }
+    Ok(())
+}
+
+// 描述路径 用于错误信息
+fn describe(path: &[PathSegment]) -> String {
+    if path.is_empty() {
+        return "the row".to_string();
+    }
+    let mut s = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) if s.is_empty() => s.push_str(key),
+            PathSegment::Key(key) => {
+                s.push('.');
+                s.push_str(key);
+            }
+            PathSegment::Index(idx) => s.push_str(&format!("[{}]", idx)),
+        }
+    }
+    format!("'{}'", s)
+}
+
+// 空值或空字符串
+fn is_blank(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        _ => false,
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "an object",
+        Value::Array(_) => "an array",
+        _ => "a scalar",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn unflatten(row: Value) -> Result<Value> {
+        let Value::Object(row) = row else {
+            unreachable!()
+        };
+        let paths: Vec<_> = row.keys().map(|k| parse_path(k, ".")).collect();
+        unflatten_row(&paths, row, 2)
+    }
+
+    #[test]
+    fn test_parse_path() {
+        use PathSegment::*;
+        assert_eq!(
+            parse_path("items[1].name", "."),
+            vec![Key("items".into()), Index(1), Key("name".into())]
+        );
+        assert_eq!(
+            parse_path("matrix[0][2]", "."),
+            vec![Key("matrix".into()), Index(0), Index(2)]
+        );
+        assert_eq!(parse_path("a[x]", "."), vec![Key("a[x]".into())]);
+        assert_eq!(parse_path("[0]", "."), vec![Key("[0]".into())]);
+    }
+
+    #[test]
+    fn test_unflatten_row() -> Result<()> {
+        let row = json!({
+            "name": "Alice",
+            "address.city": "Torino",
+            "address.zip": "10121",
+            "tags[0]": "a",
+            "tags[1]": "b",
+            "items[0].id": 1
+        });
+        assert_eq!(
+            unflatten(row)?,
+            json!({
+                "name": "Alice",
+                "address": {"city": "Torino", "zip": "10121"},
+                "tags": ["a", "b"],
+                "items": [{"id": 1}]
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unflatten_conflict() {
+        let err = unflatten(json!({"address": "x", "address.city": "Torino"})).unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("line 2"));
+        assert!(err.contains("'address' is a scalar"));
+
+        assert!(unflatten(json!({"tags.a": 1, "tags[0]": 2})).is_err());
+        // 过大的下标报错而不是分配内存
+        let err = unflatten(json!({"tags[3000000000]": "x"})).unwrap_err();
+        assert!(err.to_string().contains("column tags[3000000000]"));
+        assert!(unflatten(json!({"tags[10000]": "x"})).is_ok());
+        // 空值不冲突
+        assert_eq!(
+            unflatten(json!({"address": null, "address.city": "Torino"})).unwrap(),
+            json!({"address": {"city": "Torino"}})
+        );
+        assert_eq!(
+            unflatten(json!({"address.city": "Torino", "address": ""})).unwrap(),
+            json!({"address": {"city": "Torino"}})
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::io::{BufReader, Read, Write};
+
+use anyhow::Result;
+use quick_xml::{
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+use serde_json::{Map, Value};
+
+use super::record_writer::RecordWriter;
+
+/// 数组元素的元素名
+const ITEM: &str = "item";
+
+/// XML 每条记录一个元素，字段为子元素，数组的每一项为<item>，空值为空元素
+pub struct XmlWriter<W: Write> {
+    writer: Writer<W>,
+    root: String,
+    row: String,
+    started: bool,
+}
+
+impl<W: Write> XmlWriter<W> {
+    pub fn new(writer: W, root: &str, row: &str) -> Self {
+        Self {
+            writer: Writer::new_with_indent(writer, b' ', 2),
+            root: element_name(root),
+            row: element_name(row),
+            started: false,
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if !self.started {
+            self.writer
+                .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+            self.writer
+                .write_event(Event::Start(BytesStart::new(self.root.as_str())))?;
+            self.started = true;
+        }
+        Ok(())
+    }
+
+    fn write_value(&mut self, name: &str, value: &Value) -> Result<()> {
+        if value.is_null() {
+            self.writer
+                .write_event(Event::Empty(BytesStart::new(name)))?;
+            return Ok(());
+        }
+        self.writer
+            .write_event(Event::Start(BytesStart::new(name)))?;
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    self.write_value(&element_name(key), value)?;
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.write_value(ITEM, item)?;
+                }
+            }
+            Value::String(s) => self.writer.write_event(Event::Text(BytesText::new(s)))?,
+            scalar => self
+                .writer
+                .write_event(Event::Text(BytesText::new(&scalar.to_string())))?,
+        }
+        self.writer.write_event(Event::End(BytesEnd::new(name)))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for XmlWriter<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.start()?;
+        let row = self.row.clone();
+        self.write_value(&row, record)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.start()?;
+        self.writer
+            .write_event(Event::End(BytesEnd::new(self.root.as_str())))?;
+        let writer = self.writer.get_mut();
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 转换为合法的元素名 其他字符替换为_，不能以字母或_开头时加上_
+pub fn element_name(name: &str) -> String {
+    let mut element: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !element.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        element.insert(0, '_');
+    }
+    element
+}
+
+/// 读取根元素下的每个子元素作为一条记录
+/// 有子元素的元素为对象，子元素都是<item>时为数组，属性作为对象的字段，
+/// 空元素为null，其余为字符串
+pub fn read_xml(reader: impl Read) -> Result<Vec<Value>> {
+    let mut reader = Reader::from_reader(BufReader::new(reader));
+    let mut buf = vec![];
+    // 正在读取的元素
+    let mut stack: Vec<Element> = vec![];
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => stack.push(Element::new(&e)?),
+            Event::Empty(e) => {
+                let element = Element::new(&e)?;
+                let value = match element.children.is_empty() {
+                    true => Value::Null,
+                    false => element.into_value(),
+                };
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push((element_key(&e)?, value)),
+                    None => return Ok(vec![]),
+                }
+            }
+            Event::Text(e) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&e.unescape()?);
+                }
+            }
+            Event::CData(e) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(std::str::from_utf8(&e)?);
+                }
+            }
+            Event::End(_) => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| anyhow::anyhow!("Unexpected closing tag"))?;
+                match stack.last_mut() {
+                    Some(parent) => {
+                        let name = element.name.clone();
+                        parent.children.push((name, element.into_value()));
+                    }
+                    // 根元素结束
+                    None => return Ok(element.children.into_iter().map(|(_, v)| v).collect()),
+                }
+            }
+            Event::Eof => return Err(anyhow::anyhow!("Unexpected end of XML document")),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// 读取中的元素
+struct Element {
+    name: String,
+    children: Vec<(String, Value)>,
+    text: String,
+}
+
+impl Element {
+    fn new(start: &BytesStart) -> Result<Self> {
+        let mut children = vec![];
+        for attr in start.attributes() {
+            let attr = attr?;
+            children.push((
+                String::from_utf8(attr.key.as_ref().to_vec())?,
+                Value::String(attr.unescape_value()?.to_string()),
+            ));
+        }
+        Ok(Self {
+            name: element_key(start)?,
+            children,
+            text: String::new(),
+        })
+    }
+
+    fn into_value(self) -> Value {
+        if self.children.is_empty() {
+            return Value::String(self.text);
+        }
+        // 子元素之间的空白忽略
+        if self.children.iter().all(|(name, _)| name == ITEM) {
+            return Value::Array(self.children.into_iter().map(|(_, v)| v).collect());
+        }
+        // 重复的子元素合并为数组
+        let mut map = Map::new();
+        for (name, value) in self.children {
+            match map.get_mut(&name) {
+                Some(Value::Array(items)) => items.push(value),
+                Some(first) => *first = Value::Array(vec![first.take(), value]),
+                None => {
+                    map.insert(name, value);
+                }
+            }
+        }
+        Value::Object(map)
+    }
+}
+
+fn element_key(start: &BytesStart) -> Result<String> {
+    Ok(String::from_utf8(start.name().as_ref().to_vec())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_xml_round_trip() -> Result<()> {
+        let mut buf = Vec::new();
+        let mut writer = XmlWriter::new(&mut buf, "players", "player");
+        writer.write(&json!({"Name": "Bob <&>", "Kit Number": 1, "Tags": ["a", "b"], "Club": {"name": "Juve"}, "Note": null, "Empty": ""}))?;
+        writer.finish()?;
+        let text = String::from_utf8(buf)?;
+        assert_eq!(
+            text,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<players>
+  <player>
+    <Name>Bob &lt;&amp;&gt;</Name>
+    <Kit_Number>1</Kit_Number>
+    <Tags>
+      <item>a</item>
+      <item>b</item>
+    </Tags>
+    <Club>
+      <name>Juve</name>
+    </Club>
+    <Note/>
+    <Empty></Empty>
+  </player>
+</players>
+"#
+        );
+        assert_eq!(
+            read_xml(text.as_bytes())?,
+            vec![
+                json!({"Name": "Bob <&>", "Kit_Number": "1", "Tags": ["a", "b"], "Club": {"name": "Juve"}, "Note": null, "Empty": ""})
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_xml_attributes_and_repeats() -> Result<()> {
+        let xml = r#"<data><row id="1"><tag>a</tag><tag>b</tag></row><row id="2"/></data>"#;
+        assert_eq!(
+            read_xml(xml.as_bytes())?,
+            vec![json!({"id": "1", "tag": ["a", "b"]}), json!({"id": "2"})]
+        );
+        assert_eq!(element_name("1st col"), "_1st_col");
+        assert!(read_xml("<data><row>".as_bytes()).is_err());
+        Ok(())
+    }
+}
@@ -6,3 +6,13 @@ pub fn get_reader(input: &str) -> Result<Box<dyn std::io::Read>> {
         _ => Ok(Box::new(std::fs::File::open(input)?)),
     }
 }
+
+/// 获取Writer 带缓冲
+pub fn get_writer(output: &str) -> Result<Box<dyn std::io::Write>> {
+    match output {
+        "-" => Ok(Box::new(std::io::BufWriter::new(std::io::stdout()))),
+        _ => Ok(Box::new(std::io::BufWriter::new(std::fs::File::create(
+            output,
+        )?))),
+    }
+}